@@ -11,22 +11,23 @@ from collections import defaultdict
 cargo = ["cargo", "run", "--release", "--bin", "election2016", "--"]
 #cargo = ["cargo", "run", "--bin", "election2016", "--"]
 
-experiments = {"exp1_no-change" : 1,
-               "exp2_bump-1" : 2,
-               "exp3_bump-2" : 3,
-               "exp4_bump-3" : 4,
-               "exp5_bump-4" : 5,
-               "exp6_bump-bottom" : 6,}
-
-experiments = {"exp1_no-change" : 1,
-               "exp2_10%_chance_bump-1" : 7,
-               "exp3_25%_chance_bump-1" : 8,
-               "exp4_33%_chance_bump-1" : 9,
-               "exp5_50%_chance_bump-1" : 10,
-               "exp6_66%_chance_bump-1" : 11,
-               "exp7_75%_chance_bump-1" : 12,
-               "exp8_90%_chance_bump-1" : 13,
-               "exp9_100%_chance_bump-1" : 2,}
+# Experiment definitions live in `experiments/`, `None` counts the ballots unchanged.
+experiments = {"exp1_no-change" : None,
+               "exp2_bump-1" : "bump-1.toml",
+               "exp3_bump-2" : "bump-2.toml",
+               "exp4_bump-3" : "bump-3.toml",
+               "exp5_bump-4" : "bump-4.toml",
+               "exp6_bump-bottom" : "bump-bottom.toml",}
+
+experiments = {"exp1_no-change" : None,
+               "exp2_10%_chance_bump-1" : "chance-10-bump-1.toml",
+               "exp3_25%_chance_bump-1" : "chance-25-bump-1.toml",
+               "exp4_33%_chance_bump-1" : "chance-33-bump-1.toml",
+               "exp5_50%_chance_bump-1" : "chance-50-bump-1.toml",
+               "exp6_66%_chance_bump-1" : "chance-66-bump-1.toml",
+               "exp7_75%_chance_bump-1" : "chance-75-bump-1.toml",
+               "exp8_90%_chance_bump-1" : "chance-90-bump-1.toml",
+               "exp9_100%_chance_bump-1" : "bump-1.toml",}
 
 results = defaultdict(list)
 
@@ -46,14 +47,16 @@ def run():
 
 
 
-    for exp_name, exp_id in experiments.items():
+    for exp_name, exp_file in experiments.items():
 
         for (state, num_senators) in sorted(states.items()):
             print("Running election for {} at {}".format(state, timestamp()))
 
             state_csv = os.path.join(data_dir, "{}.csv".format(state))
 
-            args = [candidate_ordering, state_csv, state, str(num_senators), str(exp_id)]
+            args = [candidate_ordering, state_csv, state, str(num_senators)]
+            if exp_file is not None:
+                args = ["--experiment", os.path.join("experiments", exp_file)] + args
 
             #output = sp.check_output(cargo + args, stderr=sp.DEVNULL, universal_newlines=True)
             output = sp.check_output(cargo + args, universal_newlines=True)
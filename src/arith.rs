@@ -4,11 +4,14 @@ use util::*;
 use vote_map::*;
 
 /// Given a list of ballots, group them by next valid candidate.
+///
+/// Ballots without a valid preference remaining are returned separately, as exhausted.
 pub fn group_ballots_by_candidate<'a>(
     vote_map: &VoteMap<'a>,
     ballots: Vec<&'a mut Ballot>,
-) -> HashMap<CandidateId, Vec<&'a mut Ballot>> {
+) -> (HashMap<CandidateId, Vec<&'a mut Ballot>>, Vec<&'a mut Ballot>) {
     let mut map = HashMap::new();
+    let mut exhausted = vec![];
 
     for ballot in ballots {
         if let Some(i) = vote_map.find_next_valid_preference(ballot) {
@@ -18,10 +21,12 @@ pub fn group_ballots_by_candidate<'a>(
 
             let bucket = map.entry(continuing_candidate).or_insert_with(Vec::new);
             bucket.push(ballot);
+        } else {
+            exhausted.push(ballot);
         }
     }
 
-    map
+    (map, exhausted)
 }
 
 /// Compute the value of a list of ballots at a given weight (transfer value).
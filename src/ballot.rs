@@ -1,13 +1,20 @@
 use candidate::*;
 
 /// A Ballot represents an individual's order of preferences.
-#[derive(PartialEq, Eq, Hash, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Serialize, Deserialize)]
 pub struct Ballot {
     /// Ordering of candidates.
     pub prefs: Vec<CandidateId>,
+    /// Whether this was an above the line vote, in which case `prefs` lists the candidates of
+    /// each numbered group together, as split by `ElectionInfo::split_groups`. A group may be
+    /// incomplete once candidates are withdrawn or preferences truncated. Votes replaced by a
+    /// group voting ticket are marked below the line, as a ticket needn't keep groups together.
+    pub above_the_line: bool,
     /// Index of the first candidate in `prefs` who is still in the running.
+    #[serde(skip)]
     current: usize,
-    #[cfg(feature = "support2013")]
+    /// Number of voters who cast this ballot. It shares padding with `above_the_line`, so
+    /// doesn't increase the size of a `Ballot`.
     weight: u32,
 }
 
@@ -15,31 +22,33 @@ impl Ballot {
     pub fn single(prefs: Vec<CandidateId>) -> Ballot {
         Ballot {
             prefs: prefs,
+            above_the_line: false,
             current: 0,
-            #[cfg(feature = "support2013")]
             weight: 1,
         }
     }
 
-    #[cfg(feature = "support2013")]
+    /// Create a ballot from an above the line vote, with the groups' candidates in order.
+    pub fn single_above(prefs: Vec<CandidateId>) -> Ballot {
+        Ballot {
+            above_the_line: true,
+            ..Ballot::single(prefs)
+        }
+    }
+
     pub fn multi(weight: u32, prefs: Vec<CandidateId>) -> Ballot {
         Ballot {
             prefs: prefs,
+            above_the_line: false,
             current: 0,
             weight: weight,
         }
     }
 
-    #[cfg(feature = "support2013")]
     pub fn weight(&self) -> u32 {
         self.weight
     }
 
-    #[cfg(not(feature = "support2013"))]
-    pub fn weight(&self) -> u32 {
-        1
-    }
-
     pub fn current(&self) -> usize {
         self.current
     }
@@ -0,0 +1,203 @@
+//! Binary cache of parsed ballots, so that large preference files only need to be parsed once.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use bincode;
+
+use ballot::*;
+use ballot_parse::*;
+use candidate::*;
+use group::Group;
+use stats::Stats;
+
+/// Version of the cache format. Caches written with a different version are ignored.
+const CACHE_VERSION: u32 = 5;
+
+/// Identifies the inputs and parse settings that a cache was created from.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct CacheKey {
+    version: u32,
+    /// SHA-256 of the candidates file.
+    pub candidates_sha256: String,
+    /// SHA-256 of the preferences file.
+    pub prefs_sha256: String,
+    pub state: String,
+    /// Number of malformed rows tolerated while parsing (0 unless parsing leniently).
+    pub max_malformed_rows: usize,
+}
+
+impl CacheKey {
+    pub fn new(
+        candidates_sha256: &str,
+        prefs_sha256: &str,
+        state: &str,
+        max_malformed_rows: usize,
+    ) -> CacheKey {
+        CacheKey {
+            version: CACHE_VERSION,
+            candidates_sha256: candidates_sha256.to_string(),
+            prefs_sha256: prefs_sha256.to_string(),
+            state: state.to_string(),
+            max_malformed_rows: max_malformed_rows,
+        }
+    }
+}
+
+/// Everything needed to count a state's ballots without re-parsing the input files.
+#[derive(Serialize, Deserialize)]
+pub struct BallotCache {
+    pub key: CacheKey,
+    /// The state's candidates, in ballot paper order.
+    pub candidates: Vec<Candidate>,
+    /// Candidate IDs in the order of the below the line columns.
+    pub candidate_ids: Vec<CandidateId>,
+    pub groups: Vec<Group>,
+    /// Counts of valid and invalid ballots.
+    pub stats: Stats,
+    /// Descriptions of malformed rows skipped while parsing.
+    pub malformed_rows: Vec<String>,
+    /// All of the formal ballots.
+    pub ballots: Vec<Ballot>,
+}
+
+impl BallotCache {
+    /// Create a cache from a stream of parsed ballots.
+    ///
+    /// Fails with the first input error in the stream, if any.
+    pub fn new<I>(
+        key: CacheKey,
+        candidates: Vec<Candidate>,
+        candidate_ids: Vec<CandidateId>,
+        groups: Vec<Group>,
+        malformed_rows: Vec<String>,
+        ballot_stream: I,
+    ) -> Result<BallotCache, Box<Error>>
+    where
+        I: IntoIterator<Item = IOBallot>,
+    {
+        let mut stats = Stats::new();
+        let mut ballots = vec![];
+        for maybe_ballot in ballot_stream {
+            match maybe_ballot {
+                Ok(ballot) => {
+                    stats.record_valid_vote(&ballot);
+                    ballots.push(ballot);
+                }
+                Err(InvalidBallot(err)) => stats.record_invalid_vote(err),
+                Err(InputError(e)) => return Err(e),
+            }
+        }
+        Ok(BallotCache {
+            key: key,
+            candidates: candidates,
+            candidate_ids: candidate_ids,
+            groups: groups,
+            stats: stats,
+            malformed_rows: malformed_rows,
+            ballots: ballots,
+        })
+    }
+
+    /// Load a cache, returning `None` if it doesn't exist, can't be read, or doesn't match `key`.
+    pub fn load(path: &Path, key: &CacheKey) -> Option<BallotCache> {
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(_) => return None,
+        };
+        match read_cache(BufReader::new(file), key) {
+            Ok(Some(cache)) => Some(cache),
+            Ok(None) => {
+                info!("Ignoring stale ballot cache {:?}", path);
+                None
+            }
+            Err(e) => {
+                warn!("Ignoring unreadable ballot cache {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<Error>> {
+        let file = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(file, self)?;
+        Ok(())
+    }
+
+    /// Ballot stream for `decide_election` that reproduces the original parse, including the
+    /// invalid ballots (for statistics).
+    pub fn into_ballot_stream(self) -> impl Iterator<Item = IOBallot> {
+        let invalid: Vec<IOBallot> = self.stats
+            .invalid_votes()
+            .iter()
+            .flat_map(|(&err, &count)| (0..count).map(move |_| Err(InvalidBallot(err))))
+            .collect();
+        self.ballots.into_iter().map(Ok).chain(invalid)
+    }
+}
+
+/// Read a cache if its key matches, or return `None`.
+///
+/// The version is checked first, then the rest of the key, so that a cache in another format is
+/// never read further (where it could be misread, e.g. as a huge length prefix).
+fn read_cache<R: Read + Seek>(
+    mut reader: R,
+    key: &CacheKey,
+) -> Result<Option<BallotCache>, Box<Error>> {
+    let version: u32 = bincode::deserialize_from(&mut reader)?;
+    if version != CACHE_VERSION {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(0))?;
+    let cached_key: CacheKey = bincode::deserialize_from(&mut reader)?;
+    if &cached_key != key {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(0))?;
+    Ok(Some(bincode::deserialize_from(reader)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn round_trip() {
+        let key = CacheKey::new("abc", "def", "NT", 0);
+        let ballots = vec![
+            Ok(Ballot::single(vec![1, 2])),
+            Err(InvalidBallot(InvalidMinBelow(3))),
+            Ok(Ballot::single(vec![2])),
+        ];
+        let cache = BallotCache::new(key.clone(), vec![], vec![1, 2], vec![], vec![], ballots)
+            .unwrap();
+
+        let file_name = format!("aus_senate_test_round_trip_{}.cache", process::id());
+        let path = env::temp_dir().join(file_name);
+        cache.save(&path).unwrap();
+
+        assert!(BallotCache::load(&path, &CacheKey::new("abc", "xyz", "NT", 0)).is_none());
+        assert!(BallotCache::load(&path, &CacheKey::new("abc", "def", "NT", 10)).is_none());
+
+        let loaded = BallotCache::load(&path, &key).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.stats.num_valid_votes(), 2);
+        assert_eq!(loaded.stats.num_invalid_votes(), 1);
+        let stream: Vec<IOBallot> = loaded.into_ballot_stream().collect();
+        assert_eq!(stream.len(), 3);
+        assert_eq!(stream[1].as_ref().unwrap().prefs, vec![2]);
+
+        // An older version, followed by what would be a huge length prefix in this one.
+        let mut old = vec![3, 0, 0, 0];
+        old.extend(&[0xff; 8]);
+        fs::write(&path, &old).unwrap();
+        assert!(BallotCache::load(&path, &key).is_none());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -2,8 +2,6 @@ use std::collections::BTreeMap;
 use std::cmp::{Ordering, min};
 use std::cmp::Ordering::*;
 
-extern crate rand;
-
 use ballot::*;
 use candidate::*;
 use group::Group;
@@ -17,10 +15,11 @@ pub use self::CountConstraint::*;
 #[derive(Debug)]
 pub enum BallotParseErr {
     InvalidBallot(InvalidBallotErr),
-    InputError(Box<Error>),
+    // Boxed errors are thread-safe so that ballots can be parsed in parallel.
+    InputError(Box<Error + Send + Sync>),
 }
 
-#[derive(PartialEq, Eq, Hash, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum InvalidBallotErr {
     InvalidCharacter,
     InvalidMinAbove(usize),
@@ -67,6 +66,69 @@ impl Constraints {
         }
     }
 
+    /// Formality rules for the NSW Legislative Council.
+    ///
+    /// Above the line, a single group is enough and only the groups numbered are counted.
+    /// Below the line at least 15 candidates must be numbered (Constitution Act 1902, Sch 6).
+    pub fn nsw_lc() -> Constraints {
+        Constraints {
+            choice: PreferBelow,
+            counts: vec![MinAbove(1), MinBelow(15)],
+        }
+    }
+
+    /// Formality rules for the South Australian Legislative Council, since 2018.
+    ///
+    /// A single group is enough above the line, with further group preferences optional.
+    /// Below the line at least 12 candidates must be numbered (Electoral Act 1985, Section 94).
+    pub fn sa_lc() -> Constraints {
+        Constraints {
+            choice: PreferBelow,
+            counts: vec![MinAbove(1), MinBelow(12)],
+        }
+    }
+
+    /// Formality rules for the Western Australian Legislative Council, from 2025.
+    ///
+    /// A single group is enough above the line, with further group preferences optional.
+    /// Below the line at least 20 candidates must be numbered.
+    pub fn wa_lc() -> Constraints {
+        Constraints {
+            choice: PreferBelow,
+            counts: vec![MinAbove(1), MinBelow(20)],
+        }
+    }
+
+    /// Formality rules for the ACT Legislative Assembly (Hare-Clark).
+    ///
+    /// There are no group squares, and a single preference is a formal vote.
+    pub fn act() -> Constraints {
+        Constraints {
+            choice: PreferBelow,
+            counts: vec![MinBelow(1)],
+        }
+    }
+
+    /// Formality rules for the Tasmanian House of Assembly.
+    ///
+    /// At least as many candidates as there are vacancies (seven) must be numbered.
+    pub fn tas() -> Constraints {
+        Constraints {
+            choice: PreferBelow,
+            counts: vec![MinBelow(7)],
+        }
+    }
+
+    /// Formality rules for the Victorian Legislative Council below the line.
+    ///
+    /// Votes above the line follow the group's voting ticket, so aren't parsed as preferences.
+    pub fn vic_lc() -> Constraints {
+        Constraints {
+            choice: PreferBelow,
+            counts: vec![MinBelow(5)],
+        }
+    }
+
     fn check_cmp<F>(
         invalid: Ordering,
         vote_length: usize,
@@ -154,19 +216,26 @@ pub fn parse_ballot_str(
     groups: &[Group],
     candidates: &[CandidateId],
     constraints: &Constraints,
-    experiment_num: usize,
 ) -> IOBallot {
+    // Every group and candidate (including ungrouped candidates) should have a column.
+    let num_columns = pref_string.split(',').count();
+    if num_columns != groups.len() + candidates.len() {
+        return Err(InputError(From::from(format!(
+            "ballot has {} preference columns, expected {} (for {} groups and {} candidates)",
+            num_columns,
+            groups.len() + candidates.len(),
+            groups.len(),
+            candidates.len()
+        ))));
+    }
+
     // Iterator over integer preferences.
     let mut pref_iter = pref_string.split(',');
 
     let above_the_line = create_group_pref_map(pref_iter.by_ref().take(groups.len()), groups)
         .and_then(remove_repeats_and_gaps)
         .and_then(|v| constraints.check_above(v))
-        .map(|ok| flatten_group_pref_map(ok, experiment_num));
-
-    // for abl in above_the_line.iter() {
-    //     println!("{:?}", abl);
-    // }
+        .map(flatten_group_pref_map);
 
     let below_the_line = create_pref_map(pref_iter, candidates)
         .and_then(remove_repeats_and_gaps)
@@ -175,8 +244,8 @@ pub fn parse_ballot_str(
 
     match (constraints.choice, above_the_line, below_the_line) {
         (_, Ok(prefs), Err(_)) |
+        (PreferAbove, Ok(prefs), Ok(_)) => Ok(Ballot::single_above(prefs)),
         (_, Err(_), Ok(prefs)) |
-        (PreferAbove, Ok(prefs), Ok(_)) |
         (PreferBelow, Ok(_), Ok(prefs)) => Ok(Ballot::single(prefs)),
         (Strict, Ok(_), Ok(_)) => Err(InvalidBallot(InvalidStrict)),
         (_, Err(e1), Err(_)) => Err(e1),
@@ -196,193 +265,12 @@ pub fn flatten_pref_map(pref_map: PrefMap) -> Vec<CandidateId> {
     pref_map.into_iter().map(|(_, x)| x).collect()
 }
 
-pub fn flatten_group_pref_map(group_pref_map: GroupPrefMap, experiment_num: usize) -> Vec<CandidateId> {
+pub fn flatten_group_pref_map(group_pref_map: GroupPrefMap) -> Vec<CandidateId> {
     let size = group_pref_map.values().map(|x| x.len()).sum();
     let mut flat = Vec::with_capacity(size);
-    let mut orig_flat = Vec::with_capacity(size);
-
-    let mut bump = false;
-    let mut bump_amt : u32 = 0;
-
-    if experiment_num == 1 {
-        bump = false;
-    }
-    if experiment_num == 2 {
-        bump = true;
-        bump_amt = 1;
-    }
-    if experiment_num == 3 {
-        bump = true;
-        bump_amt = 2;
-    }
-    if experiment_num == 4 {
-        bump = true;
-        bump_amt = 3;
-    }
-    if experiment_num == 5 {
-        bump = true;
-        bump_amt = 4;
+    for (_, group) in group_pref_map {
+        flat.extend_from_slice(group);
     }
-    if experiment_num == 6 {
-        bump = true;
-        bump_amt = 500;
-    }
-    if experiment_num == 7 {
-        let x = rand::random::<f64>();
-        if x >= 0.9 {
-            bump = true;
-            bump_amt = 1;
-        }
-    }
-    if experiment_num == 8 {
-        let x = rand::random::<f64>();
-        if x >= 0.7555 {
-            bump = true;
-            bump_amt = 1;
-        }
-    }
-    if experiment_num == 9 {
-        let x = rand::random::<f64>();
-        if x >= 0.66 {
-            bump = true;
-            bump_amt = 1;
-        }
-    }
-    if experiment_num == 10 {
-        let x = rand::random::<f64>();
-        if x >= 0.499999 {
-            bump = true;
-            bump_amt = 1;
-        }
-    }
-    if experiment_num == 11 {
-        let x = rand::random::<f64>();
-        if x >= 0.33 {
-            bump = true;
-            bump_amt = 1;
-        }
-    }
-    if experiment_num == 12 {
-        let x = rand::random::<f64>();
-        if x >= 0.25 {
-            bump = true;
-            bump_amt = 1;
-        }
-    }
-    if experiment_num == 13 {
-        let x = rand::random::<f64>();
-        if x >= 0.1 {
-            bump = true;
-            bump_amt = 1;
-        }
-    }
-
-    let labor = [1058, 1059, 1060, 1061, 1062, 1063, 1064, 1065, 1066, 1067, 1068, 1069, 1177, 1178, 1192, 1193, 1194, 1195, 1196, 1197, 1310, 1311, 1312, 1313, 1314, 1315, 1374, 1375, 1376, 1377, 1378, 1379, 1436, 1437, 1438, 1439, 1440, 1441, 1442, 1443, 1552, 1553, 1554, 1555, 1556, 1557, 1558, 998, 999];
-    let libs = [1004, 1005, 1028, 1029, 1031, 1033, 1034, 1036, 1037, 1039, 1202, 1203, 1204, 1205, 1206, 1207, 1208, 1209, 1330, 1331, 1332, 1333, 1334, 1335, 1387, 1388, 1389, 1390, 1391, 1392, 1501, 1503, 1504, 1505, 1506, 1604, 1605, 1606, 1607, 1608, 1609, 1610];
-
-    //
-    // TODO: here is where the data looks like this:
-    // gpm: {1: [1004, 1005], 2: [1010, 1011], 3: [994, 995], 4: [1006, 1007], 5: [1002, 1003], 6: [998, 999]}
-    // Where the [1004, 1005] are potential senators from the SAME party
-    // I'm guessing the order (1, 2, 3, ...) is which one people put first
-
-    let mut orig_count = 0;
-    let mut new_count = 0;
-    let mut mid_count = 0;
-
-    if bump {
-        let mut found_lib : u32 = 0;
-        let mut found_lab : u32 = 0;
-
-        // find the indexs of the votes for the major parties
-        for (idx, group) in &group_pref_map {
-            orig_flat.extend_from_slice(group);
-            orig_count += 1;
-            for grp in group.iter() {
-                if labor.contains(&grp) {
-                    found_lab = *idx;
-                    break
-                }
-                else if libs.contains(&grp) {
-                    found_lib = *idx;
-                    break
-                }
-            }
-        }
-
-        let mut lib_cans = None;
-        let mut lab_cans = None;
-        let mut new_lib_idx = 0;
-        let mut new_lab_idx = 0;
-        let mut lib_bump = bump_amt as f32;
-        let mut lab_bump = bump_amt as f32;
-
-        let expansion_factor = 10;  // needs to be even
-        let mut bigger_idx = 0;
-        let mut new_grp_pref_map = GroupPrefMap::new();
-
-        // Expand the indexes by an expansion factor, so we can slot in new
-        // indexes inbetween them. Drop out the major parties for now.
-        for (idx, group) in &group_pref_map {
-            bigger_idx = *idx * expansion_factor;
-            if *idx == found_lib || *idx == found_lab {
-                continue;
-            }
-            mid_count += 1;
-            new_grp_pref_map.insert(bigger_idx, group);
-        }
-
-        // Handle a special case. If the majors are next to each other
-        // then the one ahead needs an extra bump to allow it to move 
-        // appropriately
-        if found_lib > 0 && found_lab > 0 {
-            if found_lab as i32 - found_lib as i32 == 1 as i32 {
-                lib_bump += 0.9;
-            } 
-            if found_lib as i32 - found_lab as i32 == 1 as i32 {
-                lab_bump += 0.9;
-            }
-        }
-
-        // Now insert the major parties back in, with a new index that slots
-        // between the other votes appropriately!
-        if found_lab > 0 {
-            new_lab_idx = (found_lab * expansion_factor) + (lab_bump * expansion_factor as f32) as u32 + (expansion_factor / 2);
-            lab_cans = group_pref_map.get(&found_lab);
-            new_grp_pref_map.insert(new_lab_idx, lab_cans.unwrap());
-        }
-
-        if found_lib > 0 {
-            new_lib_idx = (found_lib * expansion_factor) + (lib_bump * expansion_factor as f32) as u32 + (expansion_factor / 2);
-            lib_cans = group_pref_map.get(&found_lib);
-            new_grp_pref_map.insert(new_lib_idx, lib_cans.unwrap());
-        }
-
-
-        // Sort the new expanded groups
-        //let keys: Vec<_> = new_grp_pref_map.keys().cloned().collect();
-        //println!("{:?}", keys);
-
-        for (_, group) in &new_grp_pref_map {
-            // A BTreeHash is already sorted..?
-            new_count += 1;
-            flat.extend_from_slice(group);
-        }
-
-        if orig_count != new_count {
-            println!("\nOrig: {}, new: {}, mid: {}", orig_count, new_count, mid_count);
-            println!("\nfound_lab: {}, found_lib: {}", found_lab, found_lib);
-            println!("o: {:?}", orig_flat);
-            println!("n: {:?}", flat);
-        }
-
-    } else {
-        // Normal election with no bumping
-        for (_, group) in &group_pref_map {
-            flat.extend_from_slice(group);
-        }
-    }
-
     flat
 }
 
@@ -1,5 +1,6 @@
 extern crate aus_senate;
 extern crate csv;
+extern crate rand;
 
 use std::env;
 use std::error::Error;
@@ -10,32 +11,24 @@ use aus_senate::voting::*;
 use aus_senate::util::*;
 use aus_senate::ballot_parse::*;
 use aus_senate::parse::*;
-use aus_senate::parse::gvt2013::GVT;
-use aus_senate::parse::gvt_usage2013::GVTUsage;
-
-// FIXME: use iterators instead
-fn create_gvt_ballot_list(gvt: &GVT, gvt_usage: &GVTUsage, state: &str) -> Vec<IOBallot> {
-    gvt_usage[state]
-        .iter()
-        // If the vote count is 0, then we can safely skip adding this bit of GVT usage.
-        // The AEC files are strange in that some groups are included in the GVT usage with
-        // with a count of 0, but absent are from the actual GVT description.
-        .filter(|&(_, &vote_count)| vote_count != 0)
-        // We then create a ballot with the right list of preferences from the GVT description.
-        .map(|(group, &vote_count)| Ok(Ballot::multi(vote_count, gvt[state][group].clone())))
-        .collect()
-}
+use aus_senate::parse::gvt2013::ticket_ballots;
+use aus_senate::transform::seeded_rng;
 
 fn main_with_result() -> Result<(), Box<Error>> {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() != 6 {
-        println!(
-            "Usage: ./election2013 <candidates file> <gvt file> <gvt usage file> \
-             <btl votes> <state>"
-        );
-        Err("invalid command line arguments.".to_string())?
-    }
+    // Seed for splitting votes between a group's tickets.
+    let seed = match args.len() {
+        6 => rand::random::<usize>(),
+        8 if args[6] == "--seed" => args[7].parse::<usize>()?,
+        _ => {
+            println!(
+                "Usage: ./election2013 <candidates file> <gvt file> <gvt usage file> \
+                 <btl votes> <state> [--seed <seed>]"
+            );
+            Err("invalid command line arguments.".to_string())?
+        }
+    };
 
     let candidates_file_name = &args[1];
     let gvt_file_name = &args[2];
@@ -48,16 +41,22 @@ fn main_with_result() -> Result<(), Box<Error>> {
     let gvt_usage_file = open_aec_csv(gvt_usage_file_name)?;
     let gvt_usage = gvt_usage2013::parse(gvt_usage_file)?;
 
-    let btl_file = open_aec_csv(btl_file_name)?;
-    let btl_votes = btl2013::parse(btl_file)?;
-
     let candidates_file = open_aec_csv(candidates_file_name)?;
     let all_candidates = candidates2013::parse(candidates_file)?;
 
     let candidates = get_state_candidates(&all_candidates, state);
 
+    let btl_file = open_aec_csv(btl_file_name)?;
+    let (btl_votes, savings) = btl2013::parse(btl_file, candidates.len())?;
+
+    println!("Formal BTL votes: {}", savings.formal);
+    println!("BTL votes saved (last square blank): {}", savings.last_square_blank);
+    println!("BTL votes saved (sequence errors): {}", savings.sequence_errors);
+    println!("Informal BTL votes: {}", savings.informal);
+
     // Construct the initial list of ballots according to the GVT.
-    let mut ballots = create_gvt_ballot_list(&gvt, &gvt_usage, state);
+    println!("Seed: {}", seed);
+    let mut ballots = ticket_ballots(&gvt[state], &gvt_usage[state], &mut seeded_rng(seed))?;
 
     // Then extend it with the below the line votes.
     ballots.extend(btl_votes.into_iter().map(|(_, pref_map)| {
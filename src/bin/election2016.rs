@@ -1,6 +1,7 @@
-#[macro_use]
 extern crate aus_senate;
 extern crate csv;
+extern crate rayon;
+extern crate rand;
 #[macro_use]
 extern crate log;
 extern crate env_logger;
@@ -8,36 +9,116 @@ extern crate env_logger;
 use std::error::Error;
 use std::env;
 use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
 
 use aus_senate::group::*;
 use aus_senate::candidate::*;
 use aus_senate::voting::*;
 use aus_senate::ballot_parse::*;
-use aus_senate::parse::candidates2016;
+use aus_senate::parse::{candidates2016, blt};
+use aus_senate::ballot_cache::*;
+use aus_senate::parse::prefs2016::*;
+use aus_senate::manifest::*;
+use aus_senate::diagnostics::*;
+use aus_senate::experiment::Experiment;
+use aus_senate::monte_carlo::*;
+use aus_senate::transform::*;
+use aus_senate::comparison::*;
+use aus_senate::senate_result::Senate;
+use aus_senate::sweep::*;
+use aus_senate::search::*;
 
+/// Default location of the data manifest, relative to the working directory.
+const DEFAULT_MANIFEST: &str = "data_sources.json";
 
-fn main_with_result() -> Result<(), Box<Error>> {
-    env_logger::init()?;
+/// Precision of the probabilities and fractions found by `--search`.
+const SEARCH_TOLERANCE: f64 = 0.001;
 
-    let args: Vec<String> = env::args().collect();
+/// Remove a `--flag <value>` pair from the argument list, returning the value.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, Box<Error>> {
+    match args.iter().position(|a| a == flag) {
+        Some(i) if i + 1 < args.len() => {
+            let value = args.remove(i + 1);
+            args.remove(i);
+            Ok(Some(value))
+        }
+        Some(_) => Err(format!("missing value for {}", flag))?,
+        None => Ok(None),
+    }
+}
 
-    if args.len() != 4 && args.len() != 5 && args.len() != 6 {
-        println!("Usage: ./election2016 <candidates file> <prefs file> <state> [num candidates] [experiment number]");
-        Err("invalid command line arguments.".to_string())?;
+/// Remove a `--switch` from the argument list, returning whether it was present.
+fn take_switch(args: &mut Vec<String>, switch: &str) -> bool {
+    match args.iter().position(|a| a == switch) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
     }
+}
 
-    let candidates_file_name = &args[1];
-    let prefs_file_name = &args[2];
-    let state = &args[3];
-    let num_candidates = match args.get(4) {
-        Some(x) => x.parse::<usize>()?,
-        None => 0,
-    };
-    let experiment_number = match args.get(5) {
-        Some(x) => x.parse::<usize>()?,
-        None => 0,
+/// Checksum the input files, and check them against the manifest if there is one.
+///
+/// Mismatched checksums are an error unless `allow_mismatch` is set, in which case
+/// they're logged as warnings.
+fn verify_inputs(
+    manifest_file_name: Option<String>,
+    input_file_names: &[&str],
+    allow_mismatch: bool,
+) -> Result<Vec<InputChecksum>, Box<Error>> {
+    let manifest = match manifest_file_name {
+        Some(name) => load_manifest(File::open(name)?)?,
+        None if Path::new(DEFAULT_MANIFEST).exists() => {
+            load_manifest(File::open(DEFAULT_MANIFEST)?)?
+        }
+        None => {
+            warn!("No data manifest found, input files will not be verified");
+            Manifest::new()
+        }
     };
 
+    let mut checksums = vec![];
+    for file_name in input_file_names {
+        let checksum = verify_input(&manifest, Path::new(file_name))?;
+        match checksum.verification {
+            Verification::Verified => (),
+            Verification::NotInManifest => {
+                warn!("{} is not listed in the data manifest", checksum.file_name);
+            }
+            Verification::Mismatch { ref expected } => {
+                if !allow_mismatch {
+                    Err(format!(
+                        "checksum mismatch for {}: expected {}, got {}",
+                        checksum.file_name,
+                        expected,
+                        checksum.sha256
+                    ))?;
+                }
+                // Logged as an error so it's visible without setting `RUST_LOG`.
+                error!(
+                    "CHECKSUM MISMATCH for {}: expected {}, got {}. \
+                     Results will not correspond to the published data!",
+                    checksum.file_name,
+                    expected,
+                    checksum.sha256
+                );
+            }
+        }
+        checksums.push(checksum);
+    }
+    Ok(checksums)
+}
+
+/// Parse the candidates and preferences files into a set of ballots ready for counting.
+fn parse_ballots(
+    key: CacheKey,
+    candidates_file_name: &str,
+    prefs_file_name: &str,
+) -> Result<BallotCache, Box<Error>> {
+    let state = key.state.clone();
+
     let candidates_file = File::open(candidates_file_name)?;
     let all_candidates = candidates2016::parse(candidates_file)?;
 
@@ -46,23 +127,434 @@ fn main_with_result() -> Result<(), Box<Error>> {
     //}
 
     // Extract candidate and group information from the complete list of ballots.
-    let candidates = get_state_candidates(&all_candidates, state);
-    let candidate_ids = get_candidate_id_list(&all_candidates, state);
-    let groups = get_group_list(&all_candidates, state);
+    let candidates = get_state_candidates(&all_candidates, &state);
+    let candidate_ids = get_candidate_id_list(&all_candidates, &state);
+    let groups = get_group_list(&all_candidates, &state);
 
     let constraints = Constraints::official();
 
-    println!("Num groups: {}", groups.len());
-    trace!("Groups: {:#?}", groups);
-
     let prefs_file = File::open(prefs_file_name)?;
 
     let mut csv_reader = csv::ReaderBuilder::new()
         .comment(Some('-' as u8))
         .from_reader(prefs_file);
-    let ballots_iter = parse_preferences_file!(csv_reader, &groups, &candidate_ids, &constraints, experiment_number);
 
-    let election_result = decide_election(&candidates, &[], ballots_iter, num_candidates)?;
+    // Newer files label each column, so we can check the columns against the candidate list.
+    let header = csv_reader.headers()?.clone();
+    let labelled = has_labelled_columns(&header);
+    let candidate_ids = if labelled {
+        candidate_columns(&header, &groups, &candidates)?
+    } else {
+        candidate_ids
+    };
+
+    let state_candidates: Vec<Candidate> = all_candidates
+        .into_iter()
+        .filter(|c| c.state == state)
+        .collect();
+
+    let ballots = if labelled {
+        parse_rows_parallel(&mut csv_reader, DEFAULT_CHUNK_SIZE, |row| {
+            parse_labelled_ballot(row, prefs_file_name, &groups, &candidate_ids, &constraints)
+        })
+    } else {
+        parse_rows_parallel(&mut csv_reader, DEFAULT_CHUNK_SIZE, |row| {
+            parse_single_ballot(row, Some(&header), prefs_file_name, &groups, &candidate_ids, &constraints)
+        })
+    };
+
+    let mut malformed_rows = MalformedRows::new(key.max_malformed_rows);
+    let ballots: Vec<IOBallot> = lenient(ballots, &mut malformed_rows).collect();
+    let malformed_rows = malformed_rows.errors.iter().map(|e| e.to_string()).collect();
+
+    BallotCache::new(key, state_candidates, candidate_ids, groups, malformed_rows, ballots)
+}
+
+/// Print the distribution of seats from a set of trials.
+fn print_distribution(state: &str, distribution: &SeatDistribution) {
+    println!("=== Seat distribution ({} trials) ===", distribution.num_trials);
+    for p in &distribution.parties {
+        println!(
+            "{} {{{}}} mean {:.3}, min {}, max {}",
+            state,
+            p.party,
+            p.mean,
+            p.min,
+            p.max
+        );
+    }
+
+    println!("=== Compositions ===");
+    for &(ref composition, count, seed) in &distribution.compositions {
+        let parties: Vec<String> = composition
+            .iter()
+            .map(|(party, seats)| format!("{{{}}}: {}", party, seats))
+            .collect();
+        println!("{} trials (first seed {}): {}", count, seed, parties.join(", "));
+    }
+}
+
+/// Print the differences between the original count and the count of the altered ballots.
+fn print_comparison(state: &str, original: &Senate, altered: &Senate) {
+    let comparison = compare(original, altered);
+
+    println!("=== Comparison with original count ===");
+    for &(ref party, before, after) in &comparison.seats {
+        println!("{} {{{}}} {} -> {}", state, party, before, after);
+    }
+    for c in &comparison.lost {
+        println!("Lost: {} {} {{{}}}", c.other_names, c.surname, c.party);
+    }
+    for c in &comparison.gained {
+        println!("Gained: {} {} {{{}}}", c.other_names, c.surname, c.party);
+    }
+    println!(
+        "Exhausted ballots: {} -> {}",
+        original.exhausted_ballots,
+        altered.exhausted_ballots
+    );
+    println!(
+        "Exhausted votes: {} -> {}",
+        original.exhausted_votes,
+        altered.exhausted_votes
+    );
+}
+
+/// Print the candidates elected for each number of vacancies, marking the changes from the
+/// previous count.
+fn print_sweep(counts: &[VacancyCount], candidates: &CandidateMap) {
+    let mut previous: &[CandidateId] = &[];
+    for count in counts {
+        println!("=== Vacancies: {} ===", count.vacancies);
+        for (i, id) in count.elected.iter().enumerate() {
+            let c = &candidates[id];
+            let new = if previous.is_empty() || previous.contains(id) { "" } else { " (new)" };
+            println!("{}. {} {} {{{}}}{}", i + 1, c.other_names, c.surname, c.party, new);
+        }
+        for id in previous.iter().filter(|id| !count.elected.contains(id)) {
+            let c = &candidates[id];
+            println!("Dropped: {} {} {{{}}}", c.other_names, c.surname, c.party);
+        }
+        previous = &count.elected;
+    }
+}
+
+/// Print the tipping point found for each seed, and the experiment at the smallest of them.
+fn print_search(
+    search: &Search,
+    parameter: SearchParameter,
+    points: &[(usize, Option<TippingPoint>)],
+    candidates: &CandidateMap,
+) -> Result<(), Box<Error>> {
+    let describe = |id: &CandidateId| {
+        let c = &candidates[id];
+        format!("{} {} {{{}}}", c.other_names, c.surname, c.party)
+    };
+
+    println!("=== Search over {} ===", parameter);
+    for &(seed, ref point) in points {
+        let point = match *point {
+            Some(ref point) => point,
+            None => {
+                println!("Seed {}: no change", seed);
+                continue;
+            }
+        };
+        println!("Seed {}: elected candidates change at {}", seed, point.value);
+        for id in search.original().iter().filter(|id| !point.elected.contains(id)) {
+            println!("  Lost: {}", describe(id));
+        }
+        for id in point.elected.iter().filter(|id| !search.original().contains(id)) {
+            println!("  Gained: {}", describe(id));
+        }
+    }
+
+    // The smallest perturbation is the lowest probability or fraction, or the most preferences
+    // kept.
+    let found = points.iter().filter_map(|&(_, ref p)| p.as_ref());
+    let smallest = if parameter.is_level() {
+        found.max_by(|a, b| a.value.partial_cmp(&b.value).unwrap())
+    } else {
+        found.min_by(|a, b| a.value.partial_cmp(&b.value).unwrap())
+    };
+    if let Some(point) = smallest {
+        println!("=== Smallest tipping point (seed {}) ===", point.seed);
+        println!("{}", search.experiment_at(point.value)?);
+    }
+    Ok(())
+}
+
+fn main_with_result() -> Result<(), Box<Error>> {
+    env_logger::init()?;
+
+    let mut args: Vec<String> = env::args().collect();
+
+    let manifest_file_name = take_flag(&mut args, "--manifest")?;
+    let allow_checksum_mismatch = take_switch(&mut args, "--allow-checksum-mismatch");
+    let max_malformed_rows = match take_flag(&mut args, "--lenient")? {
+        Some(x) => x.parse::<usize>()?,
+        None => 0,
+    };
+    let blt_file_name = take_flag(&mut args, "--export-blt")?;
+    let blt_dedup = take_switch(&mut args, "--dedup");
+    let cache_file_name = take_flag(&mut args, "--cache")?;
+    let no_cache = take_switch(&mut args, "--no-cache");
+    let experiment_file_name = take_flag(&mut args, "--experiment")?;
+    let seed = match take_flag(&mut args, "--seed")? {
+        Some(x) => x.parse::<usize>()?,
+        None => rand::random::<usize>(),
+    };
+    let num_trials = match take_flag(&mut args, "--trials")? {
+        Some(x) => Some(x.parse::<usize>()?),
+        None => None,
+    };
+    let compare_original = take_switch(&mut args, "--compare");
+    let search_parameter = match take_flag(&mut args, "--search")? {
+        Some(x) => Some(parse_parameter(&x)?),
+        None => None,
+    };
+    let vacancies = match take_flag(&mut args, "--vacancies")? {
+        Some(x) => Some(parse_vacancies(&x)?),
+        None => None,
+    };
+    if let Some(threads) = take_flag(&mut args, "--threads")? {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.parse::<usize>()?)
+            .build_global()?;
+    }
+
+    if args.len() != 4 && args.len() != 5 {
+        println!(
+            "Usage: ./election2016 [--manifest <data_sources.json>] [--allow-checksum-mismatch] \
+             [--lenient <max malformed rows>] [--export-blt <BLT file> [--dedup]] \
+             [--cache <cache file> | --no-cache] [--threads <num threads>] \
+             [--experiment <experiment file> [--seed <seed>] [--trials <num trials> | --compare] \
+             [--search <parameter>]] [--vacancies <num | min-max>] \
+             <candidates file> <prefs file> <state> [num candidates]"
+        );
+        Err("invalid command line arguments.".to_string())?;
+    }
+
+    let candidates_file_name = &args[1];
+    let prefs_file_name = &args[2];
+    let state = &args[3];
+    let num_candidates = match args.get(4) {
+        Some(x) => x.parse::<usize>()?,
+        None => 0,
+    };
+    if blt_file_name.is_some() && num_candidates == 0 {
+        Err("--export-blt requires the number of candidates to elect")?;
+    }
+    let experiment = match experiment_file_name {
+        Some(f) => Some(Experiment::load(Path::new(&f))?),
+        None => None,
+    };
+    if num_trials.is_some() && experiment.is_none() {
+        Err("--trials requires an --experiment")?;
+    }
+    if compare_original && experiment.is_none() {
+        Err("--compare requires an --experiment")?;
+    }
+    if search_parameter.is_some() && (experiment.is_none() || compare_original) {
+        Err("--search requires an --experiment, and can't be used with --compare")?;
+    }
+    if vacancies.is_some() && (num_trials.is_some() || compare_original || search_parameter.is_some()) {
+        Err("--vacancies can't be used with --trials, --compare or --search")?;
+    }
+
+    let input_checksums = verify_inputs(
+        manifest_file_name,
+        &[candidates_file_name, prefs_file_name],
+        allow_checksum_mismatch,
+    )?;
+
+    // Ballots are cached after parsing, keyed by the checksums of the input files.
+    // Experiments are applied after loading, so the same cache is used for all of them.
+    let key = CacheKey::new(
+        &input_checksums[0].sha256,
+        &input_checksums[1].sha256,
+        state,
+        max_malformed_rows,
+    );
+    let cache_path = match cache_file_name {
+        Some(f) => PathBuf::from(f),
+        None => PathBuf::from(format!("{}.cache", prefs_file_name)),
+    };
+    let use_cache = !no_cache;
+
+    let cached = if use_cache {
+        BallotCache::load(&cache_path, &key)
+    } else {
+        None
+    };
+
+    let mut cache = match cached {
+        Some(cache) => {
+            println!("Using cached ballots from {}", cache_path.display());
+            cache
+        }
+        None => {
+            let cache = parse_ballots(key, candidates_file_name, prefs_file_name)?;
+            if use_cache {
+                if let Err(e) = cache.save(&cache_path) {
+                    warn!("Unable to write ballot cache {}: {}", cache_path.display(), e);
+                }
+            }
+            cache
+        }
+    };
+
+    // Count the untouched ballots first, if they're to be compared with the altered ones.
+    let original_result = if compare_original {
+        let candidates = get_state_candidates(&cache.candidates, state);
+        let stream = cache.ballots.iter().cloned().map(Ok);
+        Some(decide_election(&candidates, &[], stream, num_candidates)?)
+    } else {
+        None
+    };
+
+    // Withdrawn and merged parties change the candidates, so they're dealt with before the
+    // other transforms.
+    let mut scenario_counts = None;
+    if let Some(ref e) = experiment {
+        let candidates = get_state_candidates(&cache.candidates, state);
+        if let Some(scenario) = e.scenario(&cache.groups, &candidates)? {
+            scenario_counts = Some(scenario.apply(&mut cache));
+        }
+    }
+
+    let candidates = get_state_candidates(&cache.candidates, state);
+
+    println!("Num groups: {}", cache.groups.len());
+    trace!("Groups: {:#?}", cache.groups);
+
+    // Transforms applied to the ballots before counting. Parties in the experiment are found
+    // by name amongst this state's candidates.
+    let mut pipeline = Pipeline::new(seed);
+    if let Some(ref e) = experiment {
+        for transform in e.transforms(&cache.groups, &candidates, &cache.candidate_ids)? {
+            pipeline.push(transform);
+        }
+    }
+
+    // Search for the smallest change to the experiment that changes the result, for each of
+    // `num_trials` seeds.
+    if let Some(parameter) = search_parameter {
+        let info = ElectionInfo::new(&candidates, &cache.groups);
+        let search = Search::new(
+            experiment.as_ref().unwrap(),
+            parameter,
+            &info,
+            &cache.candidate_ids,
+            &cache.ballots,
+            num_candidates,
+        )?;
+        let mut points = vec![];
+        for i in 0..num_trials.unwrap_or(1) {
+            let trial_seed = seed.wrapping_add(i);
+            points.push((trial_seed, search.tipping_point(trial_seed, SEARCH_TOLERANCE)?));
+        }
+
+        println!("=== Inputs ===");
+        for input in &input_checksums {
+            println!("{}", input);
+        }
+        println!("=== Experiment ===");
+        println!("{}", experiment.as_ref().unwrap());
+        print_search(&search, parameter, &points, &candidates)?;
+        return Ok(());
+    }
+
+    if let Some(num_trials) = num_trials {
+        let seeds: Vec<usize> = (0..num_trials).map(|i| seed.wrapping_add(i)).collect();
+        let info = ElectionInfo::new(&candidates, &cache.groups);
+        let trials = run_trials(&info, &cache.ballots, num_candidates, &mut pipeline, &seeds)?;
+        let distribution = seat_distribution(&trials, &candidates);
+
+        println!("=== Inputs ===");
+        for input in &input_checksums {
+            println!("{}", input);
+        }
+        println!("=== Experiment ===");
+        println!("{}", experiment.as_ref().unwrap());
+        print_distribution(state, &distribution);
+        return Ok(());
+    }
+
+    {
+        let info = ElectionInfo::new(&candidates, &cache.groups);
+        pipeline.apply_all(&mut cache.ballots, &info);
+    }
+
+    // Count the same ballots for each number of vacancies, instead of a single count.
+    if let Some(vacancies) = vacancies {
+        let counts = vacancy_sweep(&candidates, &cache.ballots, &vacancies)?;
+
+        println!("=== Inputs ===");
+        for input in &input_checksums {
+            println!("{}", input);
+        }
+        if let Some(ref experiment) = experiment {
+            println!("=== Experiment ===");
+            println!("{}", experiment);
+            println!("Seed: {}", seed);
+        }
+        print_sweep(&counts, &candidates);
+        return Ok(());
+    }
+
+    // The exported ballots are the ones counted below, so any experiment has already been
+    // applied to them.
+    if let Some(blt_file_name) = blt_file_name {
+        let output = BufWriter::new(File::create(&blt_file_name)?);
+        blt::write(
+            output,
+            &format!("{} Senate", state),
+            &candidates,
+            &cache.candidate_ids,
+            num_candidates,
+            &cache.ballots,
+            blt_dedup,
+        )?;
+        if experiment.is_some() {
+            println!("Exported formal ballots after the experiment to {}", blt_file_name);
+        } else {
+            println!("Exported formal ballots to {}", blt_file_name);
+        }
+    }
+
+    let malformed_rows = cache.malformed_rows.clone();
+
+    let mut election_result = decide_election(&candidates, &[], cache.into_ballot_stream(), num_candidates)?;
+    election_result.inputs = input_checksums;
+    election_result.experiment = experiment;
+
+    println!("=== Inputs ===");
+    for input in &election_result.inputs {
+        println!("{}", input);
+    }
+
+    if let Some(ref experiment) = election_result.experiment {
+        println!("=== Experiment ===");
+        println!("{}", experiment);
+        println!("Seed: {}", seed);
+        if let Some(counts) = scenario_counts {
+            println!("Ballots changed by withdrawals and mergers: {}", counts.changed);
+            if counts.removed > 0 {
+                println!("Ballots removed by withdrawals: {}", counts.removed);
+            }
+        }
+        for (name, count) in pipeline.counts() {
+            println!("Ballots changed by {}: {}", name, count);
+        }
+    }
+
+    if !malformed_rows.is_empty() {
+        println!("=== Malformed rows ({} skipped) ===", malformed_rows.len());
+        for e in &malformed_rows {
+            println!("{}", e);
+        }
+    }
 
     println!("=== Elected ===");
     for &(ref c, ref votes) in &election_result.senators {
@@ -75,6 +567,10 @@ fn main_with_result() -> Result<(), Box<Error>> {
         );
     }
 
+    if let Some(ref original) = original_result {
+        print_comparison(state, original, &election_result);
+    }
+
     // if election_result.tied {
     //     println!("Tie for the last place");
     // }
@@ -84,6 +580,6 @@ fn main_with_result() -> Result<(), Box<Error>> {
 
 fn main() {
     if let Err(e) = main_with_result() {
-        error!("Error: {:?}", e);
+        error!("Error: {}", e);
     }
 }
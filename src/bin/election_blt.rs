@@ -0,0 +1,53 @@
+extern crate aus_senate;
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+use std::error::Error;
+use std::env;
+use std::fs::File;
+
+use aus_senate::voting::*;
+use aus_senate::parse::blt;
+
+fn main_with_result() -> Result<(), Box<Error>> {
+    env_logger::init()?;
+
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() != 2 && args.len() != 3 {
+        println!("Usage: ./election_blt <BLT file> [num seats]");
+        Err("invalid command line arguments.".to_string())?;
+    }
+
+    let election = blt::parse(File::open(&args[1])?)?;
+
+    // The number of seats in the file can be overridden.
+    let num_seats = match args.get(2) {
+        Some(x) => x.parse::<usize>()?,
+        None => election.num_seats,
+    };
+
+    println!("Election: {}", election.title);
+    println!("Num candidates: {}", election.candidates.len());
+
+    let election_result = decide_election(
+        &election.candidates,
+        &election.withdrawn,
+        election.ballots,
+        num_seats,
+    )?;
+
+    println!("=== Elected ===");
+    for &(ref c, ref votes) in &election_result.senators {
+        println!("{} {} [{} votes]", c.other_names, c.surname, votes);
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = main_with_result() {
+        error!("Error: {}", e);
+    }
+}
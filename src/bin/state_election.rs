@@ -0,0 +1,352 @@
+extern crate aus_senate;
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+extern crate rand;
+
+use std::error::Error;
+use std::env;
+use std::fs::File;
+
+use aus_senate::ballot_parse::*;
+use aus_senate::candidate::*;
+use aus_senate::group::*;
+use aus_senate::rules::*;
+use aus_senate::senate_result::Senate;
+use aus_senate::voting::*;
+use aus_senate::parse::{candidate_list, nsw_lc, vic_lc, sa_lc, wa_lc, act, tas};
+use aus_senate::countback::countback;
+use aus_senate::parse::gvt2013::ticket_ballots;
+use aus_senate::transform::seeded_rng;
+
+/// Remove `--seed <seed>` from the arguments, returning the seed, or a random one if absent.
+fn take_seed(args: &mut Vec<&String>) -> Result<usize, Box<Error>> {
+    let seed = match args.iter().position(|a| *a == "--seed") {
+        Some(i) if i + 1 < args.len() => {
+            let seed = args.remove(i + 1).parse::<usize>()?;
+            args.remove(i);
+            seed
+        }
+        Some(_) => Err("missing value for --seed")?,
+        None => rand::random::<usize>(),
+    };
+    println!("Seed: {}", seed);
+    Ok(seed)
+}
+
+/// Count the NSW Legislative Council election.
+///
+/// Surplus ballots are sampled at random, using `--seed` if given.
+fn nsw(args: &[String]) -> Result<Senate, Box<Error>> {
+    let mut args: Vec<&String> = args.iter().collect();
+    let seed = take_seed(&mut args)?;
+
+    if args.len() != 2 && args.len() != 3 {
+        Err("usage: nsw [--seed <seed>] <candidates file> <prefs file> [num seats]")?;
+    }
+    let num_seats = match args.get(2) {
+        Some(x) => x.parse::<usize>()?,
+        None => 21,
+    };
+
+    let all_candidates = nsw_lc::parse_candidates(File::open(args[0])?)?;
+    let groups = get_group_list(&all_candidates, nsw_lc::NSW);
+    let candidates = get_state_candidates(&all_candidates, nsw_lc::NSW);
+
+    println!("Num groups: {}", groups.len());
+
+    let ballots = nsw_lc::parse_preferences(
+        File::open(args[1])?,
+        args[1],
+        &all_candidates,
+        &groups,
+        &Constraints::nsw_lc(),
+    );
+
+    let rules = CountRules::nsw_lc().with_seed(seed);
+    decide_election_with_rules(&candidates, &[], ballots, num_seats, &rules)
+}
+
+/// Count a Victorian Legislative Council region.
+///
+/// Votes for groups with several tickets are split between them by lot, using `--seed` if given.
+fn vic(args: &[String]) -> Result<Senate, Box<Error>> {
+    let mut args: Vec<&String> = args.iter().collect();
+    let seed = take_seed(&mut args)?;
+
+    if args.len() != 5 && args.len() != 6 {
+        Err("usage: vic [--seed <seed>] <candidates file> <tickets file> <ticket votes file> \
+             <btl file> <region> [num seats]")?;
+    }
+    let region = args[4];
+    let num_seats = match args.get(5) {
+        Some(x) => x.parse::<usize>()?,
+        None => 5,
+    };
+
+    let all_candidates = vic_lc::parse_candidates(File::open(&args[0])?)?;
+    let positions = vic_lc::candidate_positions(&all_candidates);
+    let candidates = get_state_candidates(&all_candidates, region);
+    let candidate_ids = get_candidate_id_list(&all_candidates, region);
+
+    let gvt = vic_lc::parse_tickets(File::open(&args[1])?, &positions)?;
+    let ticket_votes = vic_lc::parse_ticket_votes(File::open(&args[2])?)?;
+
+    let tickets = gvt.get(region).ok_or_else(|| format!("no voting tickets for {}", region))?;
+    let votes = ticket_votes.get(region).ok_or_else(|| format!("no ticket votes for {}", region))?;
+
+    // Ticket votes, followed by the below the line votes.
+    let mut ballots = ticket_ballots(tickets, votes, &mut seeded_rng(seed))?;
+    ballots.extend(vic_lc::parse_btl(
+        File::open(args[3])?,
+        args[3],
+        region,
+        &candidate_ids,
+        &positions,
+        &Constraints::vic_lc(),
+    ));
+
+    decide_election_with_rules(&candidates, &[], ballots, num_seats, &CountRules::vic_lc())
+}
+
+/// Count the South Australian Legislative Council election.
+fn sa(args: &[String]) -> Result<Senate, Box<Error>> {
+    if args.len() != 2 && args.len() != 3 {
+        Err("usage: sa <candidates file> <prefs file> [num seats]")?;
+    }
+    let num_seats = match args.get(2) {
+        Some(x) => x.parse::<usize>()?,
+        None => 11,
+    };
+
+    let all_candidates = candidate_list::parse(File::open(&args[0])?, sa_lc::SA)?;
+    let groups = get_group_list(&all_candidates, sa_lc::SA);
+    let candidates = get_state_candidates(&all_candidates, sa_lc::SA);
+    let candidate_ids = get_candidate_id_list(&all_candidates, sa_lc::SA);
+
+    println!("Num groups: {}", groups.len());
+
+    let ballots = sa_lc::parse_preferences(
+        File::open(&args[1])?,
+        &args[1],
+        &groups,
+        &candidate_ids,
+        &Constraints::sa_lc(),
+    )?;
+
+    decide_election_with_rules(&candidates, &[], ballots, num_seats, &CountRules::sa_lc())
+}
+
+/// Count the Western Australian Legislative Council election.
+fn wa(args: &[String]) -> Result<Senate, Box<Error>> {
+    if args.len() != 2 && args.len() != 3 {
+        Err("usage: wa <candidates file> <prefs file> [num seats]")?;
+    }
+    let num_seats = match args.get(2) {
+        Some(x) => x.parse::<usize>()?,
+        None => 37,
+    };
+
+    let all_candidates = candidate_list::parse(File::open(&args[0])?, wa_lc::WA)?;
+    let groups = get_group_list(&all_candidates, wa_lc::WA);
+    let candidates = get_state_candidates(&all_candidates, wa_lc::WA);
+
+    println!("Num groups: {}", groups.len());
+
+    let ballots = wa_lc::parse_preferences(
+        File::open(&args[1])?,
+        &args[1],
+        &groups,
+        &candidates,
+        &Constraints::wa_lc(),
+    )?;
+
+    decide_election_with_rules(&candidates, &[], ballots, num_seats, &CountRules::wa_lc())
+}
+
+/// Fill the vacancy left by the member with surname `surname`, by a countback (or recount)
+/// of their quota papers among all the unsuccessful candidates. Ties are broken using `seed`.
+fn fill_vacancy(
+    result: &Senate,
+    candidates: &CandidateMap,
+    candidate_ids: &[CandidateId],
+    surname: &str,
+    seed: usize,
+) -> Result<(), Box<Error>> {
+    let vacating = result
+        .senators
+        .iter()
+        .find(|&&(ref c, _)| c.surname.to_lowercase() == surname.to_lowercase())
+        .map(|&(ref c, _)| c.id)
+        .ok_or_else(|| format!("no elected member named {}", surname))?;
+
+    let contesting: Vec<_> = candidate_ids
+        .iter()
+        .cloned()
+        .filter(|&id| !result.is_elected(id))
+        .collect();
+
+    let filled = countback(result, vacating, &contesting, &mut seeded_rng(seed))?;
+    let c = &candidates[&filled.elected];
+    println!("=== Countback ===");
+    println!(
+        "{} {} {{{}}} [{} of {} votes]",
+        c.other_names,
+        c.surname,
+        c.party,
+        filled.votes.floor(),
+        filled.total.floor()
+    );
+    Ok(())
+}
+
+/// Count an ACT Legislative Assembly electorate, optionally filling a vacancy by countback.
+fn act(args: &[String]) -> Result<Senate, Box<Error>> {
+    let mut args: Vec<&String> = args.iter().collect();
+    let countback = match args.iter().position(|a| *a == "--countback") {
+        Some(i) if i + 1 < args.len() => {
+            let surname = args.remove(i + 1);
+            args.remove(i);
+            Some(surname)
+        }
+        Some(_) => Err("missing value for --countback")?,
+        None => None,
+    };
+    let seed = match countback {
+        Some(_) => take_seed(&mut args)?,
+        None => 0,
+    };
+
+    if args.len() != 5 {
+        Err("usage: act <electorates file> <groups file> <candidates file> <ballots file> \
+             <electorate> [--countback <surname of vacating member> [--seed <seed>]]")?;
+    }
+    let electorate_name = args[4];
+
+    let electorates = act::parse_electorates(File::open(args[0])?)?;
+    let electorate = electorates
+        .iter()
+        .find(|e| &e.electorate == electorate_name)
+        .ok_or_else(|| format!("unknown electorate: {}", electorate_name))?;
+
+    let (all_candidates, codes) =
+        act::parse_candidates(File::open(args[1])?, File::open(args[2])?, &electorates)?;
+    let candidates = get_state_candidates(&all_candidates, electorate_name);
+    let candidate_ids = get_candidate_id_list(&all_candidates, electorate_name);
+
+    let ballots = act::parse_ballots(
+        File::open(args[3])?,
+        args[3],
+        electorate.ecode,
+        &candidate_ids,
+        &codes,
+        &Constraints::act(),
+    );
+
+    let result =
+        decide_election_with_rules(&candidates, &[], ballots, electorate.seats, &CountRules::act())?;
+
+    if let Some(vacating) = countback {
+        fill_vacancy(&result, &candidates, &candidate_ids, vacating, seed)?;
+    }
+
+    Ok(result)
+}
+
+/// Count a Tasmanian House of Assembly division.
+///
+/// With `--senate-rules` the same ballots are counted using the Senate's rules instead.
+fn tas(args: &[String]) -> Result<Senate, Box<Error>> {
+    let senate_rules = args.iter().any(|a| a == "--senate-rules");
+    let mut args: Vec<&String> = args.iter().filter(|a| *a != "--senate-rules").collect();
+
+    let recount = match args.iter().position(|a| *a == "--recount") {
+        Some(i) if i + 1 < args.len() => {
+            let surname = args.remove(i + 1);
+            args.remove(i);
+            Some(surname)
+        }
+        Some(_) => Err("missing value for --recount")?,
+        None => None,
+    };
+    let seed = match recount {
+        Some(_) => take_seed(&mut args)?,
+        None => 0,
+    };
+
+    if args.len() != 3 && args.len() != 4 {
+        Err("usage: tas [--senate-rules] [--recount <surname of vacating member> [--seed <seed>]] \
+             <candidates file> <prefs file> <division> [num seats]")?;
+    }
+    let division = args[2];
+    let num_seats = match args.get(3) {
+        Some(x) => x.parse::<usize>()?,
+        None => 7,
+    };
+
+    let all_candidates = candidate_list::parse(File::open(args[0])?, division)?;
+    let candidates = get_state_candidates(&all_candidates, division);
+    let candidate_ids = get_candidate_id_list(&all_candidates, division);
+
+    let ballots = tas::parse_preferences(
+        File::open(args[1])?,
+        args[1],
+        &candidate_ids,
+        &Constraints::tas(),
+    )?;
+
+    let rules = if senate_rules {
+        CountRules::senate()
+    } else {
+        CountRules::tas()
+    };
+
+    let result = decide_election_with_rules(&candidates, &[], ballots, num_seats, &rules)?;
+
+    if let Some(surname) = recount {
+        fill_vacancy(&result, &candidates, &candidate_ids, surname, seed)?;
+    }
+
+    Ok(result)
+}
+
+fn main_with_result() -> Result<(), Box<Error>> {
+    env_logger::init()?;
+
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        println!("Usage: ./state_election <election> <args...>");
+        println!("Elections: nsw, vic, sa, wa, act, tas");
+        Err("invalid command line arguments.".to_string())?;
+    }
+
+    let election_result = match &args[1][..] {
+        "nsw" => nsw(&args[2..])?,
+        "vic" => vic(&args[2..])?,
+        "sa" => sa(&args[2..])?,
+        "wa" => wa(&args[2..])?,
+        "act" => act(&args[2..])?,
+        "tas" => tas(&args[2..])?,
+        other => Err(format!("unknown election: {}", other))?,
+    };
+
+    println!("=== Elected ===");
+    for &(ref c, ref votes) in &election_result.senators {
+        println!(
+            "{} {} {{{}}} [{} votes]",
+            c.other_names,
+            c.surname,
+            c.party,
+            votes
+        );
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = main_with_result() {
+        error!("Error: {}", e);
+    }
+}
@@ -4,8 +4,13 @@ use std::fmt::{Debug, Formatter, Error};
 /// Integer representing a candidate.
 pub type CandidateId = u16;
 
+/// Ticket name used by the AEC for candidates who aren't part of a group.
+///
+/// Ungrouped candidates can only be voted for below the line.
+pub const UNGROUPED: &str = "UG";
+
 /// Description of a candidate including name and party affiliation.
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Candidate {
     pub id: CandidateId,
     pub surname: String,
@@ -22,6 +27,17 @@ pub struct CandidateName {
     pub last: String,
 }
 
+impl Candidate {
+    pub fn is_ungrouped(&self) -> bool {
+        self.group_name == UNGROUPED
+    }
+}
+
+/// Sort key for ticket names in ballot paper order: A-Z, then AA, AB, etc, then ungrouped.
+pub fn ticket_order(ticket: &str) -> (bool, usize, &str) {
+    (ticket == UNGROUPED, ticket.len(), ticket)
+}
+
 impl Debug for Candidate {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         write!(fmt, "{} {}", self.other_names, self.surname)
@@ -40,12 +56,15 @@ pub fn get_state_candidates(all_candidates: &[Candidate], state: &str) -> Candid
 }
 
 /// Get the list of candidate IDs, in canonical order (used for parsing).
+///
+/// This is the order of the below the line columns on the ballot paper: grouped candidates
+/// in the order given, followed by all the ungrouped candidates.
 pub fn get_candidate_id_list(all_candidates: &[Candidate], state: &str) -> Vec<CandidateId> {
-    all_candidates
+    let (grouped, ungrouped): (Vec<&Candidate>, Vec<&Candidate>) = all_candidates
         .iter()
         .filter(|c| &c.state == state)
-        .map(|c| c.id)
-        .collect()
+        .partition(|c| !c.is_ungrouped());
+    grouped.into_iter().chain(ungrouped).map(|c| c.id).collect()
 }
 
 /// Convert a list of candidate names into a list of candidate IDs.
@@ -62,3 +81,25 @@ pub fn find_candidates_with_names(
         })
         .collect()
 }
+
+/// A candidate without a name, for tests.
+#[cfg(test)]
+pub fn test_candidate(id: CandidateId, group_name: &str, party: &str) -> Candidate {
+    Candidate {
+        id: id,
+        surname: String::new(),
+        other_names: String::new(),
+        group_name: group_name.to_string(),
+        party: party.to_string(),
+        state: "NT".to_string(),
+    }
+}
+
+/// Candidates with the given IDs and no group or party, for tests.
+#[cfg(test)]
+pub fn test_candidates<I>(ids: I) -> CandidateMap
+where
+    I: IntoIterator<Item = CandidateId>,
+{
+    ids.into_iter().map(|id| (id, test_candidate(id, "", ""))).collect()
+}
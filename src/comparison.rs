@@ -0,0 +1,95 @@
+//! Comparison of a count of altered ballots with the count of the original ballots.
+
+use candidate::*;
+use monte_carlo::Composition;
+use senate_result::Senate;
+
+#[derive(Debug)]
+pub struct Comparison {
+    /// Seats won by each party in the original and altered counts, for every party that won
+    /// a seat in either.
+    pub seats: Vec<(String, usize, usize)>,
+    /// Candidates elected in the original count but not the altered one.
+    pub lost: Vec<Candidate>,
+    /// Candidates elected in the altered count but not the original one.
+    pub gained: Vec<Candidate>,
+}
+
+impl Comparison {
+    /// Whether the same candidates were elected, in any order.
+    pub fn same_elected(&self) -> bool {
+        self.lost.is_empty() && self.gained.is_empty()
+    }
+}
+
+fn composition(result: &Senate) -> Composition {
+    let mut composition = Composition::new();
+    for &(ref c, _) in &result.senators {
+        *composition.entry(c.party.clone()).or_insert(0) += 1;
+    }
+    composition
+}
+
+fn elected_only_in(a: &Senate, b: &Senate) -> Vec<Candidate> {
+    a.senators
+        .iter()
+        .filter(|&&(ref c, _)| !b.is_elected(c.id))
+        .map(|&(ref c, _)| c.clone())
+        .collect()
+}
+
+pub fn compare(original: &Senate, altered: &Senate) -> Comparison {
+    let before = composition(original);
+    let after = composition(altered);
+
+    let mut parties: Vec<&String> = before.keys().chain(after.keys()).collect();
+    parties.sort();
+    parties.dedup();
+
+    let seats = parties
+        .into_iter()
+        .map(|party| {
+            let count = |c: &Composition| c.get(party).cloned().unwrap_or(0);
+            (party.clone(), count(&before), count(&after))
+        })
+        .collect();
+
+    Comparison {
+        seats: seats,
+        lost: elected_only_in(original, altered),
+        gained: elected_only_in(altered, original),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use util::*;
+
+    #[test]
+    fn seat_changes() {
+        let candidates: CandidateMap = [(0, "Red"), (1, "Blue"), (2, "Red"), (3, "Green")]
+            .iter()
+            .map(|&(id, party)| (id, test_candidate(id, "", party)))
+            .collect();
+        let senate = |elected: &[CandidateId]| {
+            let mut result = Senate::new();
+            for &id in elected {
+                result.add_senator(id, Int::from(1), &candidates);
+            }
+            result
+        };
+
+        let same = compare(&senate(&[0, 1]), &senate(&[1, 0]));
+        assert!(same.same_elected());
+
+        let changed = compare(&senate(&[0, 1]), &senate(&[0, 3]));
+        assert!(!changed.same_elected());
+        assert_eq!(changed.lost[0].id, 1);
+        assert_eq!(changed.gained[0].id, 3);
+        assert_eq!(
+            changed.seats,
+            vec![("Blue".to_string(), 1, 0), ("Green".to_string(), 0, 1), ("Red".to_string(), 1, 1)]
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Filling casual vacancies by a countback of the departing member's ballots (Hare-Clark).
+
+use std::error::Error;
+
+use rand::Rng;
+
+use util::*;
+use candidate::*;
+use senate_result::*;
+
+/// Result of a countback: the candidate chosen, and their final share of the ballots.
+#[derive(Debug)]
+pub struct Countback {
+    pub elected: CandidateId,
+    pub votes: Frac,
+    pub total: Frac,
+}
+
+/// Fill the vacancy left by `vacating` from the result of a completed count.
+///
+/// The ballots making up the departing member's quota are counted among the `contesting`
+/// candidates (usually the unsuccessful candidates who nominate). The candidate with the fewest
+/// votes is excluded until one has an absolute majority of the unexhausted ballots. Ties that
+/// remain after looking at the first round are broken by lot, using `rng`.
+pub fn countback<R: Rng>(
+    result: &Senate,
+    vacating: CandidateId,
+    contesting: &[CandidateId],
+    rng: &mut R,
+) -> Result<Countback, Box<Error>> {
+    let papers = result.quota_papers.get(&vacating).ok_or_else(|| {
+        format!("no quota papers recorded for candidate {}", vacating)
+    })?;
+
+    if contesting.is_empty() {
+        Err("no candidates are contesting the vacancy")?;
+    }
+
+    let mut continuing: Vec<CandidateId> = contesting.to_vec();
+    // First round tallies, used to break ties.
+    let mut first_round: Option<HashMap<CandidateId, Frac>> = None;
+
+    loop {
+        let mut tally: HashMap<CandidateId, Frac> =
+            continuing.iter().map(|&id| (id, frac!(0))).collect();
+
+        for &(ref value, ref prefs) in papers {
+            if let Some(id) = prefs.iter().find(|id| continuing.contains(id)) {
+                *tally.get_mut(id).unwrap() += value;
+            }
+        }
+
+        let total = tally.values().fold(frac!(0), |acc, v| acc + v);
+
+        let (&leader, leader_votes) = tally
+            .iter()
+            .max_by(|&(_, v1), &(_, v2)| v1.cmp(v2))
+            .unwrap();
+
+        if continuing.len() == 1 || leader_votes * frac!(2) > total {
+            return Ok(Countback {
+                elected: leader,
+                votes: leader_votes.clone(),
+                total: total,
+            });
+        }
+
+        // Exclude the candidate with the fewest votes, breaking ties on the first round tally,
+        // then by lot.
+        let first = first_round.get_or_insert_with(|| tally.clone());
+        let min_key = |id: &CandidateId| (tally[id].clone(), first[id].clone());
+        let min_value = continuing.iter().map(&min_key).min().unwrap();
+        let lowest: Vec<CandidateId> = continuing
+            .iter()
+            .cloned()
+            .filter(|id| min_key(id) == min_value)
+            .collect();
+        let excluded = *rng.choose(&lowest).unwrap();
+
+        continuing.retain(|&id| id != excluded);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ballot::Ballot;
+    use rules::CountRules;
+    use transform::seeded_rng;
+    use voting::decide_election_with_rules;
+
+    #[test]
+    fn majority_after_exclusion() {
+        let mut result = Senate::new();
+        let papers = vec![
+            (frac!(1), vec![0, 1]),
+            (frac!(1), vec![0, 1]),
+            (frac!(1, 2), vec![0, 2]),
+            (frac!(1, 2), vec![0, 2]),
+            (frac!(1, 2), vec![0, 2]),
+            (frac!(1), vec![0, 3, 2]),
+            (frac!(1, 2), vec![0]),
+        ];
+        result.quota_papers.insert(0, papers);
+
+        let countback = countback(&result, 0, &[1, 2, 3], &mut seeded_rng(0)).unwrap();
+        assert_eq!(countback.elected, 2);
+        assert_eq!(countback.votes, frac!(5, 2));
+        assert_eq!(countback.total, frac!(9, 2));
+    }
+
+    #[test]
+    fn elected_without_quota() {
+        let candidates = test_candidates(0..4);
+        let ballots: Vec<(usize, Vec<CandidateId>)> = vec![
+            (5, vec![0]),
+            (3, vec![1, 2]),
+            (2, vec![2, 3]),
+            (1, vec![3, 2]),
+        ];
+        let stream = ballots
+            .iter()
+            .flat_map(|&(n, ref prefs)| (0..n).map(move |_| Ok(Ballot::single(prefs.clone()))));
+
+        // 1 fills the second seat with 3 votes, short of the quota of 4, once 2 and 3 are
+        // excluded.
+        let result = decide_election_with_rules(&candidates, &[], stream, 2, &CountRules::tas())
+            .unwrap();
+        assert!(result.is_elected(1));
+        assert_eq!(result.quota_papers[&1].len(), 3);
+
+        let countback = countback(&result, 1, &[2, 3], &mut seeded_rng(0)).unwrap();
+        assert_eq!(countback.elected, 2);
+        assert_eq!(countback.total, frac!(3));
+    }
+
+    #[test]
+    fn tie_broken_by_seed() {
+        let mut result = Senate::new();
+        let papers = vec![(frac!(1), vec![0, 1]), (frac!(1), vec![0, 2]), (frac!(1), vec![0, 3])];
+        result.quota_papers.insert(0, papers);
+
+        let elected: Vec<CandidateId> = (0..10)
+            .map(|seed| {
+                let first = countback(&result, 0, &[1, 2, 3], &mut seeded_rng(seed)).unwrap();
+                let second = countback(&result, 0, &[1, 2, 3], &mut seeded_rng(seed)).unwrap();
+                assert_eq!(first.elected, second.elected);
+                first.elected
+            })
+            .collect();
+        assert!(elected.iter().any(|&id| id != elected[0]));
+    }
+}
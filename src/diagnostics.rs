@@ -0,0 +1,144 @@
+//! Error reporting for malformed input rows.
+
+use std::error::Error;
+use std::fmt;
+
+use ballot_parse::*;
+
+/// An input error, annotated with the location of the row that caused it.
+#[derive(Debug)]
+pub struct RowError {
+    pub file_name: String,
+    /// Line number within the file, if known.
+    pub line: Option<u64>,
+    /// Batch and paper numbers of the ballot, if the row could be read.
+    pub ballot_id: Option<(String, String)>,
+    pub error: Box<Error + Send + Sync>,
+}
+
+impl RowError {
+    pub fn new<E>(file_name: &str, line: Option<u64>, error: E) -> RowError
+    where
+        E: Into<Box<Error + Send + Sync>>,
+    {
+        RowError {
+            file_name: file_name.to_string(),
+            line: line,
+            ballot_id: None,
+            error: error.into(),
+        }
+    }
+
+    pub fn with_ballot_id(mut self, batch: &str, paper: &str) -> RowError {
+        self.ballot_id = Some((batch.to_string(), paper.to_string()));
+        self
+    }
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file_name)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if let Some((ref batch, ref paper)) = self.ballot_id {
+            write!(f, " (batch {}, paper {})", batch, paper)?;
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+impl Error for RowError {
+    fn description(&self) -> &str {
+        "malformed input row"
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        Some(&*self.error)
+    }
+}
+
+/// Report of malformed rows skipped during lenient parsing.
+#[derive(Debug)]
+pub struct MalformedRows {
+    /// Maximum number of malformed rows to skip before giving up.
+    pub max_errors: usize,
+    pub errors: Vec<Box<Error + Send + Sync>>,
+}
+
+impl MalformedRows {
+    pub fn new(max_errors: usize) -> MalformedRows {
+        MalformedRows {
+            max_errors: max_errors,
+            errors: vec![],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Iterator adaptor that records input errors in a report rather than failing immediately.
+///
+/// Once the report holds `max_errors` errors, the next input error is passed through so that
+/// the count is aborted as usual.
+pub struct Lenient<'r, I> {
+    ballots: I,
+    report: &'r mut MalformedRows,
+}
+
+pub fn lenient<'r, I>(ballots: I, report: &'r mut MalformedRows) -> Lenient<'r, I::IntoIter>
+where
+    I: IntoIterator<Item = IOBallot>,
+{
+    Lenient {
+        ballots: ballots.into_iter(),
+        report: report,
+    }
+}
+
+impl<'r, I> Iterator for Lenient<'r, I>
+where
+    I: Iterator<Item = IOBallot>,
+{
+    type Item = IOBallot;
+
+    fn next(&mut self) -> Option<IOBallot> {
+        loop {
+            match self.ballots.next() {
+                Some(Err(InputError(e))) => {
+                    if self.report.errors.len() >= self.report.max_errors {
+                        return Some(Err(InputError(e)));
+                    }
+                    warn!("Skipping malformed row: {}", e);
+                    self.report.errors.push(e);
+                }
+                x => return x,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ballot::Ballot;
+
+    fn input_error() -> IOBallot {
+        Err(InputError(From::from("bad row")))
+    }
+
+    #[test]
+    fn lenient_limit() {
+        let ballots = vec![input_error(), Ok(Ballot::single(vec![1])), input_error()];
+
+        let mut report = MalformedRows::new(2);
+        assert!(lenient(ballots, &mut report).all(|b| b.is_ok()));
+        assert_eq!(report.errors.len(), 2);
+
+        let ballots = vec![input_error(), Ok(Ballot::single(vec![1])), input_error()];
+        let mut report = MalformedRows::new(1);
+        assert!(lenient(ballots, &mut report).any(|b| b.is_err()));
+    }
+}
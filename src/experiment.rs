@@ -0,0 +1,500 @@
+//! Declarative descriptions of experiments that alter the order of preferences on ballots.
+//!
+//! An experiment is written in TOML (or JSON, for files ending in `.json`), for example:
+//!
+//! ```toml
+//! name = "25% of voters move the majors down one place"
+//! probability = 0.25
+//! above_the_line = true
+//! below_the_line = false
+//!
+//! [parties]
+//! majors = ["Australian Labor Party*", "Liberal", "The Nationals"]
+//!
+//! [[moves]]
+//! party = "majors"
+//! positions = 1
+//! ```
+//!
+//! Parties are described by patterns matched against each candidate's party name (see `party`).
+//! The built-in parties `labor`, `coalition` and `greens` can be used without being listed.
+//!
+//! Each ballot is altered with the given probability, in which case every move is applied to it.
+//! Above the line votes are moved a group at a time, and below the line votes a candidate at a
+//! time.
+//!
+//! An experiment can also swing first preferences from one party to another (see `swing`):
+//!
+//! ```toml
+//! [[swings]]
+//! from = "labor"
+//! to = "greens"
+//! fraction = 0.02
+//! method = "swap"
+//! ```
+//!
+//! Or truncate ballots after their first few preferences (see `truncate`):
+//!
+//! ```toml
+//! [[truncations]]
+//! above_the_line = 6
+//! below_the_line = 12
+//! fraction = 0.5
+//! ```
+//!
+//! Or withdraw parties, or merge pairs of parties into joint tickets (see `scenario`):
+//!
+//! ```toml
+//! withdraw = ["greens"]
+//!
+//! [[mergers]]
+//! first = "labor"
+//! second = "greens"
+//! order = "interleave"
+//! ```
+//!
+//! Or simulate the group voting tickets used before 2016 (see `ticket`):
+//!
+//! ```toml
+//! [tickets]
+//! ranking = ["labor", "greens", "coalition"]
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use rand::{Rng, StdRng};
+use serde_json;
+use toml;
+
+use ballot::Ballot;
+use candidate::*;
+use group::Group;
+use party::*;
+use transform::*;
+use swing::*;
+use truncate::*;
+use scenario::*;
+use ticket::*;
+
+fn default_probability() -> f64 {
+    1.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Move a party's groups (or candidates) further down each ballot's preferences.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PartyMove {
+    /// The party to move, from the experiment's `parties` or the built-in parties.
+    pub party: String,
+    /// Number of other groups (or candidates) the party is moved below.
+    pub positions: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Experiment {
+    #[serde(default)]
+    pub name: String,
+    /// Chance that any given ballot is altered.
+    #[serde(default = "default_probability")]
+    pub probability: f64,
+    /// Whether to alter above the line votes.
+    #[serde(default = "default_true")]
+    pub above_the_line: bool,
+    /// Whether to alter below the line votes.
+    #[serde(default)]
+    pub below_the_line: bool,
+    /// Parties whose candidates are removed from the ballot paper.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub withdraw: Vec<String>,
+    /// Patterns for party names, for each party used by the moves.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parties: BTreeMap<String, Vec<String>>,
+    /// Group voting tickets for short above the line votes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tickets: Option<TicketVoting>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub moves: Vec<PartyMove>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub swings: Vec<Swing>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub truncations: Vec<Truncation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mergers: Vec<Merger>,
+}
+
+impl Experiment {
+    /// Load an experiment from a TOML file, or a JSON file if the extension is `.json`.
+    pub fn load(path: &Path) -> Result<Experiment, Box<Error>> {
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+
+        if path.extension().map_or(false, |ext| ext == "json") {
+            Experiment::from_json(&contents)
+        } else {
+            Experiment::from_toml(&contents)
+        }
+    }
+
+    pub fn from_toml(input: &str) -> Result<Experiment, Box<Error>> {
+        let experiment: Experiment = toml::from_str(input)?;
+        experiment.check()?;
+        Ok(experiment)
+    }
+
+    pub fn from_json(input: &str) -> Result<Experiment, Box<Error>> {
+        let experiment: Experiment = serde_json::from_str(input)?;
+        experiment.check()?;
+        Ok(experiment)
+    }
+
+    fn check(&self) -> Result<(), Box<Error>> {
+        if !(self.probability >= 0.0 && self.probability <= 1.0) {
+            Err(format!("probability must be between 0 and 1, not {}", self.probability))?;
+        }
+        for m in &self.moves {
+            self.party_patterns(&m.party)?;
+        }
+        for swing in &self.swings {
+            if !(swing.fraction >= 0.0 && swing.fraction <= 1.0) {
+                Err(format!("swing fraction must be between 0 and 1, not {}", swing.fraction))?;
+            }
+            self.party_patterns(&swing.from)?;
+            self.party_patterns(&swing.to)?;
+        }
+        for party in &self.withdraw {
+            self.party_patterns(party)?;
+        }
+        if let Some(ref tickets) = self.tickets {
+            for party in &tickets.ranking {
+                self.party_patterns(party)?;
+            }
+            if tickets.max_groups == Some(0) {
+                Err("max_groups for tickets must be at least 1")?;
+            }
+        }
+        for merger in &self.mergers {
+            self.party_patterns(&merger.first)?;
+            self.party_patterns(&merger.second)?;
+        }
+        for truncation in &self.truncations {
+            if !(truncation.fraction >= 0.0 && truncation.fraction <= 1.0) {
+                Err(format!(
+                    "truncation fraction must be between 0 and 1, not {}",
+                    truncation.fraction
+                ))?;
+            }
+            if truncation.above_the_line.is_none() && truncation.below_the_line.is_none() {
+                Err("truncation must limit above or below the line votes")?;
+            }
+            if truncation.above_the_line == Some(0) || truncation.below_the_line == Some(0) {
+                Err("truncation must keep at least one preference")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn party_patterns(&self, party: &str) -> Result<Vec<String>, Box<Error>> {
+        match self.parties.get(party) {
+            Some(patterns) => Ok(patterns.clone()),
+            None => Ok(builtin_patterns(party)
+                .ok_or_else(|| format!("unknown party in experiment: {}", party))?),
+        }
+    }
+
+    /// Find a party's candidates amongst a single state's candidates.
+    fn find_party(
+        &self,
+        party: &str,
+        groups: &[Group],
+        candidates: &CandidateMap,
+    ) -> Result<HashSet<CandidateId>, Box<Error>> {
+        let found = party_candidates(&self.party_patterns(party)?, groups, candidates);
+        if found.is_empty() {
+            warn!("No candidates for party {} in experiment", party);
+        }
+        Ok(found)
+    }
+
+    /// Find the candidates of each party to be moved, amongst a single state's candidates.
+    pub fn resolve(
+        &self,
+        groups: &[Group],
+        candidates: &CandidateMap,
+    ) -> Result<StateExperiment, Box<Error>> {
+        let mut moves = vec![];
+        for m in &self.moves {
+            moves.push((self.find_party(&m.party, groups, candidates)?, m.positions));
+        }
+        Ok(StateExperiment {
+            name: self.name.clone(),
+            probability: self.probability,
+            above_the_line: self.above_the_line,
+            below_the_line: self.below_the_line,
+            moves: moves,
+        })
+    }
+
+    /// Find the single group of a party to be merged.
+    fn find_group(
+        &self,
+        party: &str,
+        groups: &[Group],
+        candidates: &CandidateMap,
+    ) -> Result<usize, Box<Error>> {
+        let found = party_groups(&self.party_patterns(party)?, groups, candidates);
+        if found.len() != 1 {
+            Err(format!("party {} to be merged has {} groups, not 1", party, found.len()))?;
+        }
+        Ok(groups.iter().position(|g| g.name == found[0].name).unwrap())
+    }
+
+    /// Create the scenario of withdrawn and merged parties for a single state, if there is one.
+    pub fn scenario(
+        &self,
+        groups: &[Group],
+        candidates: &CandidateMap,
+    ) -> Result<Option<Scenario>, Box<Error>> {
+        if self.withdraw.is_empty() && self.mergers.is_empty() {
+            return Ok(None);
+        }
+        let mut withdrawn = HashSet::new();
+        for party in &self.withdraw {
+            withdrawn.extend(self.find_party(party, groups, candidates)?);
+        }
+        let mut mergers = vec![];
+        for merger in &self.mergers {
+            let first = self.find_group(&merger.first, groups, candidates)?;
+            let second = self.find_group(&merger.second, groups, candidates)?;
+            if first == second {
+                Err(format!("can't merge {} with itself", merger.first))?;
+            }
+            mergers.push((first, second, merger.order));
+        }
+        Ok(Some(Scenario::new(withdrawn, &mergers, groups)))
+    }
+
+    /// Create the transforms for the experiment, for a single state.
+    ///
+    /// `candidate_ids` gives the state's candidates in ballot paper order. Group voting tickets
+    /// are applied first, so the other changes are made to the ballots as they'd have been cast
+    /// under the old rules.
+    pub fn transforms(
+        &self,
+        groups: &[Group],
+        candidates: &CandidateMap,
+        candidate_ids: &[CandidateId],
+    ) -> Result<Vec<Box<BallotTransform>>, Box<Error>> {
+        let mut transforms: Vec<Box<BallotTransform>> = vec![];
+        if let Some(ref tickets) = self.tickets {
+            let state = candidates.values().next().map_or("", |c| &c.state[..]);
+            let mut ranked = vec![];
+            for party in &tickets.ranking {
+                let patterns = self.party_patterns(party)?;
+                for group in party_groups(&patterns, groups, candidates) {
+                    ranked.push(groups.iter().position(|g| g.name == group.name).unwrap());
+                }
+            }
+            let transform = TicketTransform::new(tickets, state, &ranked, groups, candidate_ids)?;
+            transforms.push(Box::new(transform));
+        }
+        if !self.moves.is_empty() {
+            transforms.push(Box::new(self.resolve(groups, candidates)?));
+        }
+        for swing in &self.swings {
+            let from = self.find_party(&swing.from, groups, candidates)?;
+            let to = self.find_party(&swing.to, groups, candidates)?;
+            transforms.push(Box::new(SwingTransform::new(swing, from, to, candidate_ids)));
+        }
+        for truncation in &self.truncations {
+            transforms.push(Box::new(TruncateTransform::new(truncation)));
+        }
+        Ok(transforms)
+    }
+
+    /// Whether the experiment gives the same result every time it's run (i.e. doesn't use `rand`).
+    pub fn is_deterministic(&self) -> bool {
+        let certain = |p: f64| p == 0.0 || p == 1.0;
+        (self.moves.is_empty() || certain(self.probability)) && self.tickets.is_none() &&
+            self.swings.iter().all(|s| certain(s.fraction)) &&
+            self.truncations.iter().all(|t| certain(t.fraction))
+    }
+}
+
+/// The experiment is echoed in TOML format.
+impl fmt::Display for Experiment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let spec = toml::to_string(self).map_err(|_| fmt::Error)?;
+        write!(f, "{}", spec.trim_end())
+    }
+}
+
+/// An experiment with its parties resolved to the candidates of a single state.
+#[derive(Debug, Clone)]
+pub struct StateExperiment {
+    name: String,
+    probability: f64,
+    above_the_line: bool,
+    below_the_line: bool,
+    /// Each party's candidates, and the number of positions to move them.
+    moves: Vec<(HashSet<CandidateId>, u32)>,
+}
+
+impl BallotTransform for StateExperiment {
+    fn name(&self) -> String {
+        if self.name.is_empty() {
+            "experiment".to_string()
+        } else {
+            self.name.clone()
+        }
+    }
+
+    fn transform(&self, ballot: &mut Ballot, info: &ElectionInfo, rng: &mut StdRng) -> bool {
+        let alter = if ballot.above_the_line {
+            self.above_the_line
+        } else {
+            self.below_the_line
+        };
+        // Only draw random numbers for ballots that could be altered.
+        if !alter || (self.probability < 1.0 && rng.gen::<f64>() >= self.probability) {
+            return false;
+        }
+
+        let new_prefs = {
+            let units = info.units(ballot);
+            let offsets: Vec<_> = units.iter().map(|u| self.offset(u)).collect();
+            bump(units, &offsets).concat()
+        };
+        replace_prefs(ballot, new_prefs)
+    }
+}
+
+impl StateExperiment {
+    /// Number of positions to move a group with the given candidates, if it is moved at all.
+    fn offset(&self, group: &[CandidateId]) -> Option<u32> {
+        self.moves
+            .iter()
+            .find(|&&(ref party, _)| group.iter().any(|c| party.contains(c)))
+            .map(|&(_, positions)| positions)
+    }
+}
+
+/// Move items down the list, skipping over `offsets[i]` of the items that aren't moved.
+///
+/// Items that aren't moved keep their relative order, as do moved items that land together.
+fn bump<T>(items: Vec<T>, offsets: &[Option<u32>]) -> Vec<T> {
+    let mut num_unmoved: i64 = 0;
+    let mut keyed: Vec<_> = items
+        .into_iter()
+        .zip(offsets)
+        .map(|(item, offset)| {
+            // Moved items go after the `num_unmoved + n`th unmoved item.
+            let key = match *offset {
+                Some(n) => (num_unmoved + n as i64 - 1, true),
+                None => {
+                    num_unmoved += 1;
+                    (num_unmoved - 1, false)
+                }
+            };
+            (key, item)
+        })
+        .collect();
+    keyed.sort_by_key(|&(key, _)| key);
+    keyed.into_iter().map(|(_, item)| item).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use transform::TestElection;
+
+    #[test]
+    fn bump_majors() {
+        let lab = Some(1);
+        let lib = Some(1);
+        assert_eq!(bump(vec![1, 2, 3, 4], &[lab, None, lib, None]), vec![2, 1, 4, 3]);
+        // Adjacent majors both move below the next group.
+        assert_eq!(bump(vec![1, 2, 3, 4], &[lab, lib, None, None]), vec![3, 1, 2, 4]);
+        assert_eq!(bump(vec![1, 2, 3], &[None, Some(500), None]), vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn parse_spec() {
+        let spec = "
+            name = \"bump\"
+            probability = 0.5
+
+            [[moves]]
+            party = \"labor\"
+            positions = 2
+
+            [[swings]]
+            from = \"labor\"
+            to = \"greens\"
+            fraction = 1.0
+            method = \"insert\"
+
+            [[truncations]]
+            above_the_line = 6
+
+            [[mergers]]
+            first = \"labor\"
+            second = \"greens\"
+
+            [tickets]
+            ranking = [\"greens\", \"labor\"]
+        ";
+        let experiment = Experiment::from_toml(spec).unwrap();
+        assert!(experiment.above_the_line && !experiment.below_the_line);
+        assert_eq!(experiment.swings[0].method, Insert);
+        assert_eq!(experiment.truncations[0].below_the_line, None);
+        assert_eq!(experiment.truncations[0].fraction, 1.0);
+        assert_eq!(experiment.mergers[0].order, Interleave);
+        assert_eq!(experiment.tickets.as_ref().unwrap().ranking.len(), 2);
+        assert!(!experiment.is_deterministic());
+        assert_eq!(Experiment::from_toml(&experiment.to_string()).unwrap(), experiment);
+        assert!(Experiment::from_toml(&spec.replace("labor", "pirates")).is_err());
+        assert!(Experiment::from_toml(&spec.replace("= 6", "= 0")).is_err());
+    }
+
+    #[test]
+    fn resolve_parties() {
+        let spec = "
+            below_the_line = true
+            withdraw = [\"blue\"]
+
+            [parties]
+            blue = [\"Blue*\"]
+
+            [[moves]]
+            party = \"blue\"
+            positions = 1
+        ";
+        let experiment = Experiment::from_toml(spec).unwrap();
+        assert_eq!(Experiment::from_toml(&experiment.to_string()).unwrap(), experiment);
+
+        let election = TestElection::with_parties(&[
+            ("A", "Red"),
+            ("B", "Blue Party"),
+            ("B", ""),
+            ("UG", "Blue Independents"),
+        ]);
+        let state = experiment.resolve(&election.groups, &election.candidates).unwrap();
+
+        let info = election.info();
+        let mut rng = seeded_rng(0);
+        let mut above = Ballot::single_above(vec![1, 2, 0]);
+        assert!(state.transform(&mut above, &info, &mut rng));
+        assert_eq!(above.prefs, vec![0, 1, 2]);
+        let mut below = Ballot::single(vec![3, 2, 0, 1]);
+        assert!(state.transform(&mut below, &info, &mut rng));
+        assert_eq!(below.prefs, vec![0, 3, 2, 1]);
+    }
+}
@@ -1,14 +1,17 @@
 use candidate::*;
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Group {
     pub name: String,
     pub candidate_ids: Vec<CandidateId>,
 }
 
+/// Get the list of groups that can be voted for above the line, in ballot paper order.
+///
+/// Ungrouped candidates are never part of a group.
 pub fn get_group_list(candidates: &[Candidate], state: &str) -> Vec<Group> {
     let mut groups: Vec<Group> = vec![];
-    for c in candidates.iter().filter(|c| c.state == state) {
+    for c in candidates.iter().filter(|c| c.state == state && !c.is_ungrouped()) {
         // If there's already a group for this candidate, add them and continue.
         if let Some(current_group) = groups.last_mut() {
             if current_group.name == c.group_name {
@@ -16,13 +19,11 @@ pub fn get_group_list(candidates: &[Candidate], state: &str) -> Vec<Group> {
                 continue;
             }
         }
-        // Otherwise, push a new group to the list (skipping the ungrouped group).
-        if c.group_name != "UG" {
-            groups.push(Group {
-                name: c.group_name.clone(),
-                candidate_ids: vec![c.id],
-            });
-        }
+        // Otherwise, push a new group to the list.
+        groups.push(Group {
+            name: c.group_name.clone(),
+            candidate_ids: vec![c.id],
+        });
     }
     groups
 }
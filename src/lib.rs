@@ -17,6 +17,11 @@ extern crate serde_derive;
 extern crate unwrap;
 extern crate itertools;
 extern crate rand;
+extern crate serde_json;
+extern crate sha2;
+extern crate bincode;
+extern crate rayon;
+extern crate toml;
 
 #[macro_use]
 pub mod util;
@@ -24,10 +29,26 @@ pub mod candidate;
 pub mod group;
 pub mod ballot;
 pub mod voting;
+pub mod rules;
+pub mod countback;
+pub mod party;
+pub mod transform;
+pub mod swing;
+pub mod truncate;
+pub mod scenario;
+pub mod ticket;
+pub mod experiment;
+pub mod monte_carlo;
+pub mod comparison;
+pub mod sweep;
+pub mod search;
 mod vote_map;
 pub mod ballot_parse;
 pub mod parse;
 pub mod senate_result;
 pub mod stats;
+pub mod manifest;
+pub mod diagnostics;
+pub mod ballot_cache;
 mod arith;
 mod vote_log;
@@ -0,0 +1,147 @@
+//! Verification of input files against the checksums listed in `data_sources.json`.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use sha2::{Sha256, Digest};
+
+use util::*;
+
+/// Description of a single downloadable input file, as used by `fetch_data.py`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct DataSource {
+    pub state: Option<String>,
+    #[serde(default)]
+    pub zipped: bool,
+    pub url: String,
+    #[serde(rename = "inner-file")]
+    pub inner_file: Option<String>,
+    #[serde(rename = "zip-sha256")]
+    pub zip_sha256: Option<String>,
+    pub sha256: Option<String>,
+}
+
+/// Map from local file names (e.g. `NSW.csv`) to their sources and checksums.
+pub type Manifest = BTreeMap<String, DataSource>;
+
+/// Outcome of checking a single input file against the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    Verified,
+    Mismatch { expected: String },
+    NotInManifest,
+}
+
+/// Checksum of an input file that a result was computed from.
+#[derive(Debug, Clone)]
+pub struct InputChecksum {
+    pub file_name: String,
+    pub sha256: String,
+    pub verification: Verification,
+}
+
+impl InputChecksum {
+    pub fn is_ok(&self) -> bool {
+        self.verification == Verification::Verified
+    }
+}
+
+impl fmt::Display for InputChecksum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} sha256:{}", self.file_name, self.sha256)?;
+        match self.verification {
+            Verification::Verified => write!(f, " (verified)"),
+            Verification::Mismatch { ref expected } => {
+                write!(f, " (MISMATCH, expected {})", expected)
+            }
+            Verification::NotInManifest => write!(f, " (not in manifest)"),
+        }
+    }
+}
+
+pub fn load_manifest<R: Read>(input: R) -> Result<Manifest, Box<Error>> {
+    Ok(::serde_json::from_reader(input)?)
+}
+
+/// Compute the hex-encoded SHA-256 checksum of everything in `input`.
+pub fn sha256sum<R: Read>(mut input: R) -> io::Result<String> {
+    let mut hasher = Sha256::default();
+    let mut buf = vec![0; 65536];
+    loop {
+        let n = input.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.input(&buf[..n]);
+    }
+    Ok(hasher.result().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Checksum a file and compare it to the manifest entry with the same file name.
+///
+/// Files ending in `.zip` are checked against the `zip-sha256` of the entry for the
+/// unzipped name, matching the layout of the `data` directory created by `fetch_data.py`.
+pub fn verify_input(manifest: &Manifest, path: &Path) -> io::Result<InputChecksum> {
+    let file_name = path.file_name()
+        .map(|f| f.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string_lossy().into_owned());
+
+    let sha256 = sha256sum(File::open(path)?)?;
+
+    let expected = if file_name.ends_with(".zip") {
+        manifest
+            .get(file_name.trim_end_matches(".zip"))
+            .and_then(|source| source.zip_sha256.clone())
+    } else {
+        manifest
+            .get(&file_name)
+            .and_then(|source| source.sha256.clone())
+    };
+
+    let verification = match expected {
+        Some(ref expected) if expected == &sha256 => Verification::Verified,
+        Some(expected) => Verification::Mismatch { expected: expected },
+        None => Verification::NotInManifest,
+    };
+
+    Ok(InputChecksum {
+        file_name: file_name,
+        sha256: sha256,
+        verification: verification,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty_checksum() {
+        assert_eq!(
+            sha256sum(&b""[..]).unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn parse_manifest() {
+        let json = r#"{
+            "NT.csv": {
+                "state": "NT",
+                "zipped": true,
+                "url": "http://example.com/NT.zip",
+                "inner-file": "aec-NT.csv",
+                "zip-sha256": "abc",
+                "sha256": "def"
+            }
+        }"#;
+        let manifest = load_manifest(json.as_bytes()).unwrap();
+        let nt = &manifest["NT.csv"];
+        assert_eq!(nt.state.as_ref().unwrap(), "NT");
+        assert_eq!(nt.zip_sha256.as_ref().unwrap(), "abc");
+        assert_eq!(nt.sha256.as_ref().unwrap(), "def");
+    }
+}
@@ -0,0 +1,145 @@
+//! Repeated trials of randomised ballot transforms, from a single parse of the ballots.
+//!
+//! Each trial uses its own seed, so any individual trial can be reproduced.
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+use ballot::Ballot;
+use candidate::*;
+use transform::*;
+use voting::decide_election;
+
+/// Number of seats won by each party.
+pub type Composition = BTreeMap<String, usize>;
+
+/// Outcome of a single trial.
+#[derive(Debug, Clone)]
+pub struct Trial {
+    pub seed: usize,
+    /// Elected candidates, in the order they were elected.
+    pub elected: Vec<CandidateId>,
+    /// Number of ballots changed by each transform.
+    pub changed: Vec<(String, usize)>,
+}
+
+impl Trial {
+    pub fn composition(&self, candidates: &CandidateMap) -> Composition {
+        let mut composition = Composition::new();
+        for id in &self.elected {
+            *composition.entry(candidates[id].party.clone()).or_insert(0) += 1;
+        }
+        composition
+    }
+}
+
+/// Run a trial for each seed, transforming a fresh copy of the ballots each time.
+pub fn run_trials(
+    info: &ElectionInfo,
+    ballots: &[Ballot],
+    num_positions: usize,
+    pipeline: &mut Pipeline,
+    seeds: &[usize],
+) -> Result<Vec<Trial>, Box<Error>> {
+    let mut trials = vec![];
+    for &seed in seeds {
+        pipeline.reseed(seed);
+        let result = {
+            let stream = pipeline.transform_stream(ballots.iter().cloned().map(Ok), info);
+            decide_election(info.candidates, &[], stream, num_positions)?
+        };
+        info!("Trial with seed {} changed {:?} ballots", seed, pipeline.counts());
+
+        trials.push(Trial {
+            seed: seed,
+            elected: result.senators.iter().map(|&(ref c, _)| c.id).collect(),
+            changed: pipeline.counts(),
+        });
+    }
+    Ok(trials)
+}
+
+/// Summary of one party's seats across all trials.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartySeats {
+    pub party: String,
+    pub mean: f64,
+    pub min: usize,
+    pub max: usize,
+}
+
+/// Distribution of seats across all trials.
+#[derive(Debug)]
+pub struct SeatDistribution {
+    pub num_trials: usize,
+    /// Every party that won a seat in at least one trial.
+    pub parties: Vec<PartySeats>,
+    /// Each composition of seats, with the number of trials it occurred in and the seed of the
+    /// first of those trials. Most frequent first.
+    pub compositions: Vec<(Composition, usize, usize)>,
+}
+
+pub fn seat_distribution(trials: &[Trial], candidates: &CandidateMap) -> SeatDistribution {
+    let compositions: Vec<Composition> = trials.iter().map(|t| t.composition(candidates)).collect();
+
+    let mut seats: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for composition in &compositions {
+        for party in composition.keys() {
+            seats.entry(party).or_insert_with(Vec::new);
+        }
+    }
+    for composition in &compositions {
+        for (party, party_seats) in &mut seats {
+            party_seats.push(composition.get(*party).cloned().unwrap_or(0));
+        }
+    }
+
+    let parties = seats
+        .into_iter()
+        .map(|(party, party_seats)| PartySeats {
+            party: party.to_string(),
+            mean: party_seats.iter().sum::<usize>() as f64 / party_seats.len() as f64,
+            min: party_seats.iter().cloned().min().unwrap_or(0),
+            max: party_seats.iter().cloned().max().unwrap_or(0),
+        })
+        .collect();
+
+    let mut frequencies: HashMap<&Composition, (usize, usize)> = HashMap::new();
+    for (composition, trial) in compositions.iter().zip(trials) {
+        frequencies.entry(composition).or_insert((0, trial.seed)).0 += 1;
+    }
+    let mut frequencies: Vec<_> = frequencies
+        .into_iter()
+        .map(|(composition, (count, seed))| (composition.clone(), count, seed))
+        .collect();
+    frequencies.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+
+    SeatDistribution {
+        num_trials: trials.len(),
+        parties: parties,
+        compositions: frequencies,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn distribution() {
+        let candidates: CandidateMap = [(0, "Red"), (1, "Blue"), (2, "Red")]
+            .iter()
+            .map(|&(id, party)| (id, test_candidate(id, "", party)))
+            .collect();
+        let trial = |seed, elected| Trial { seed: seed, elected: elected, changed: vec![] };
+        let trials = vec![trial(5, vec![0, 1]), trial(6, vec![2, 0]), trial(7, vec![1, 0])];
+
+        let dist = seat_distribution(&trials, &candidates);
+        assert_eq!(dist.parties[0], PartySeats { party: "Blue".into(), mean: 2.0 / 3.0, min: 0, max: 1 });
+        assert_eq!(dist.parties[1].min, 1);
+        assert_eq!(dist.parties[1].max, 2);
+        assert_eq!(dist.compositions[0].1, 2);
+        assert_eq!(dist.compositions[0].2, 5);
+        assert_eq!(dist.compositions[1].2, 6);
+    }
+}
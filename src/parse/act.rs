@@ -0,0 +1,218 @@
+//! Parsers for ACT Legislative Assembly data published by Elections ACT.
+//!
+//! Ballot papers are Robson rotated, but the preference files identify each candidate by party
+//! and candidate code rather than by position on the paper, so no rotation needs undoing. The
+//! CSV files used are:
+//!
+//! * Electorates: `ecode,electorate,seats`
+//! * Groups: `ecode,pcode,pname,pabbrev`
+//! * Candidates: `ecode,pcode,ccode,cname`, with names written as `Surname, Given Names`
+//! * Ballots (one file per electorate): `batch,pindex,pref,pcode,ccode`, one row per
+//!   preference, with the rows for each ballot paper consecutive.
+
+use csv;
+
+use super::prelude::*;
+use super::marks::PaperMarks;
+use diagnostics::RowError;
+
+/// Map from (electorate code, party code, candidate code) to candidate ID.
+pub type CandidateCodes = HashMap<(u32, u32, u32), CandidateId>;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Electorate {
+    pub ecode: u32,
+    pub electorate: String,
+    pub seats: usize,
+}
+
+#[derive(Deserialize, Debug)]
+struct GroupRow {
+    ecode: u32,
+    pcode: u32,
+    pname: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct CandidateRow {
+    ecode: u32,
+    pcode: u32,
+    ccode: u32,
+    cname: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct BallotRow {
+    batch: String,
+    pindex: String,
+    pref: String,
+    pcode: u32,
+    ccode: u32,
+}
+
+pub fn parse_electorates<R: Read>(input: R) -> Result<Vec<Electorate>, Box<Error>> {
+    let mut reader = csv::Reader::from_reader(input);
+    let mut electorates = vec![];
+    for row in reader.deserialize::<Electorate>() {
+        electorates.push(row?);
+    }
+    Ok(electorates)
+}
+
+/// Parse the candidates of all electorates, in ballot paper order (before rotation).
+///
+/// The electorate name is stored as the candidate's `state` and the party code as their group.
+pub fn parse_candidates<R1: Read, R2: Read>(
+    groups_input: R1,
+    candidates_input: R2,
+    electorates: &[Electorate],
+) -> Result<(Vec<Candidate>, CandidateCodes), Box<Error>> {
+    let mut party_names = HashMap::new();
+    let mut reader = csv::Reader::from_reader(groups_input);
+    for row in reader.deserialize::<GroupRow>() {
+        let row: GroupRow = row?;
+        party_names.insert((row.ecode, row.pcode), row.pname);
+    }
+
+    let electorate_names: HashMap<u32, &str> = electorates
+        .iter()
+        .map(|e| (e.ecode, &e.electorate[..]))
+        .collect();
+
+    let mut rows = vec![];
+    let mut reader = csv::Reader::from_reader(candidates_input);
+    for row in reader.deserialize::<CandidateRow>() {
+        rows.push(row?);
+    }
+    rows.sort_by_key(|r| (r.ecode, r.pcode, r.ccode));
+
+    let mut candidates = vec![];
+    let mut codes = HashMap::new();
+    for (id, row) in rows.into_iter().enumerate() {
+        let electorate = electorate_names
+            .get(&row.ecode)
+            .ok_or_else(|| format!("unknown electorate code {}", row.ecode))?;
+        let party = party_names.get(&(row.ecode, row.pcode)).cloned().unwrap_or_default();
+
+        let mut names = row.cname.splitn(2, ',');
+        let surname = names.next().unwrap_or("").trim().to_string();
+        let other_names = names.next().unwrap_or("").trim().to_string();
+
+        codes.insert((row.ecode, row.pcode, row.ccode), id as CandidateId);
+        candidates.push(Candidate {
+            id: id as CandidateId,
+            surname: surname,
+            other_names: other_names,
+            group_name: row.pcode.to_string(),
+            party: party,
+            state: electorate.to_string(),
+        });
+    }
+
+    Ok((candidates, codes))
+}
+
+/// Parse the ballots for a single electorate.
+///
+/// `candidate_ids` lists the electorate's candidates, as returned by `get_candidate_id_list`.
+pub fn parse_ballots<R: Read>(
+    input: R,
+    file_name: &str,
+    ecode: u32,
+    candidate_ids: &[CandidateId],
+    codes: &CandidateCodes,
+    constraints: &Constraints,
+) -> Vec<IOBallot> {
+    let columns: HashMap<CandidateId, usize> = candidate_ids
+        .iter()
+        .enumerate()
+        .map(|(idx, &id)| (id, idx))
+        .collect();
+
+    let to_ballot = |prefs: String| parse_ballot_str(&prefs, &[], candidate_ids, constraints);
+
+    let mut reader = csv::Reader::from_reader(input);
+    let headers = match reader.headers() {
+        Ok(h) => h.clone(),
+        Err(e) => return vec![Err(InputError(Box::new(RowError::new(file_name, None, e))))],
+    };
+
+    let mut ballots = vec![];
+    let mut paper = PaperMarks::new(candidate_ids.len());
+
+    for raw_row in reader.records() {
+        let record = match raw_row {
+            Ok(r) => r,
+            Err(e) => {
+                ballots.push(Err(InputError(Box::new(RowError::new(file_name, None, e)))));
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line());
+
+        let column = record
+            .deserialize::<BallotRow>(Some(&headers))
+            .map_err(|e| e.to_string())
+            .and_then(|row| {
+                let col = codes
+                    .get(&(ecode, row.pcode, row.ccode))
+                    .and_then(|id| columns.get(id))
+                    .cloned()
+                    .ok_or_else(|| format!("no candidate with codes {}/{}", row.pcode, row.ccode))?;
+                Ok((row, col))
+            });
+
+        match column {
+            Ok((row, col)) => {
+                let paper_id = format!("{}/{}", row.batch, row.pindex);
+                if let Some(prefs) = paper.next_paper(&paper_id) {
+                    ballots.push(to_ballot(prefs));
+                }
+                paper.mark(col, &row.pref);
+            }
+            Err(e) => {
+                ballots.push(Err(InputError(Box::new(RowError::new(file_name, line, e)))));
+            }
+        }
+    }
+
+    if let Some(prefs) = paper.finish() {
+        ballots.push(to_ballot(prefs));
+    }
+
+    ballots
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ballot::Ballot;
+
+    #[test]
+    fn parse_electorate() {
+        let electorates = parse_electorates("ecode,electorate,seats\n1,Brindabella,5\n".as_bytes()).unwrap();
+        let (candidates, codes) = parse_candidates(
+            "ecode,pcode,pname,pabbrev\n1,1,Red Party,RED\n1,2,Blue Party,BLU\n".as_bytes(),
+            "ecode,pcode,ccode,cname\n1,2,1,\"Jones, Bob\"\n1,1,2,\"Brown, Sam\"\n1,1,1,\"Smith, Jane\"\n".as_bytes(),
+            &electorates,
+        ).unwrap();
+        assert_eq!(candidates[0].surname, "Smith");
+        assert_eq!(candidates[2].party, "Blue Party");
+        assert_eq!(candidates[2].state, "Brindabella");
+
+        let candidate_ids = get_candidate_id_list(&candidates, "Brindabella");
+        let ballots = "\
+batch,pindex,pref,pcode,ccode
+1,1,1,2,1
+1,1,2,1,2
+1,2,1,1,1
+1,2,1,1,2
+";
+        let constraints = Constraints { choice: PreferBelow, counts: vec![MinBelow(1)] };
+        let ballots = parse_ballots(ballots.as_bytes(), "b.txt", 1, &candidate_ids, &codes, &constraints);
+        assert_eq!(ballots.len(), 2);
+        assert_eq!(*ballots[0].as_ref().unwrap(), Ballot::single(vec![2, 1]));
+        assert!(ballots[1].is_err());
+        assert_eq!(codes[&(1, 1, 1)], 0);
+    }
+}
@@ -0,0 +1,325 @@
+//! Parser for the BLT ballot format used by OpenSTV and the ERS test corpora.
+//!
+//! A BLT file looks like this:
+//!
+//! ```text
+//! 4 2             <- number of candidates, number of seats
+//! -3              <- optional list of withdrawn candidates
+//! 3 1 2 0         <- weight, preferences (numbered from 1), terminating 0
+//! 1 4 0
+//! 0               <- end of ballots
+//! "Alice"         <- candidate names, in order
+//! "Bob"
+//! "Carol"
+//! "Dave"
+//! "Title"
+//! ```
+//!
+//! Ballot lines may start with an ID in parentheses, e.g. `(17) 1 3 2 0`, which is ignored.
+
+use std::io::Write;
+
+use ballot::*;
+use super::prelude::*;
+
+/// An election described by a BLT file.
+pub struct BltElection {
+    pub title: String,
+    pub candidates: CandidateMap,
+    pub num_seats: usize,
+    /// Candidates who withdrew, to be excluded before the count.
+    pub withdrawn: Vec<CandidateId>,
+    pub ballots: Vec<IOBallot>,
+}
+
+impl BltElection {
+    /// Candidate IDs in BLT order.
+    pub fn candidate_ids(&self) -> Vec<CandidateId> {
+        (0..self.candidates.len() as CandidateId).collect()
+    }
+}
+
+/// Convert a 1-based BLT candidate number to a `CandidateId`.
+fn candidate_id(num: i64, num_candidates: usize) -> Result<CandidateId, Box<Error>> {
+    if num < 1 || num as usize > num_candidates {
+        Err(format!("invalid candidate number: {}", num))?;
+    }
+    Ok((num - 1) as CandidateId)
+}
+
+fn parse_num(token: &str) -> Result<i64, Box<Error>> {
+    token
+        .parse::<i64>()
+        .map_err(|_| From::from(format!("invalid number in BLT file: {:?}", token)))
+}
+
+/// Parse a ballot line (without the ballot ID), returning the weight and preferences.
+fn parse_ballot_line(
+    line: &str,
+    num_candidates: usize,
+) -> Result<(u32, Result<Vec<CandidateId>, InvalidBallotErr>), Box<Error>> {
+    let mut tokens = line.split_whitespace();
+
+    let weight = tokens.next().ok_or("empty ballot line")?;
+    let weight = weight
+        .parse::<u32>()
+        .map_err(|_| format!("unsupported ballot weight: {:?}", weight))?;
+
+    let mut prefs = vec![];
+    let mut terminated = false;
+    for token in tokens {
+        if terminated {
+            Err(format!("ballot continues after terminating 0: {:?}", line))?;
+        }
+        if token == "-" {
+            continue;
+        }
+        if token.contains('=') {
+            Err(format!("equal rankings are not supported: {:?}", line))?;
+        }
+        match parse_num(token)? {
+            0 => terminated = true,
+            num => prefs.push(candidate_id(num, num_candidates)?),
+        }
+    }
+
+    if !terminated {
+        Err(format!("ballot line missing terminating 0: {:?}", line))?;
+    }
+
+    // If a candidate is repeated, ignore that preference and any later preferences.
+    if let Some(cutoff) = (1..prefs.len()).find(|&i| prefs[..i].contains(&prefs[i])) {
+        prefs.truncate(cutoff);
+    }
+
+    if prefs.is_empty() {
+        Ok((weight, Err(EmptyBallot)))
+    } else {
+        Ok((weight, Ok(prefs)))
+    }
+}
+
+/// Strip the quotes from a candidate name or title.
+fn unquote(line: &str) -> &str {
+    line.trim().trim_matches('"')
+}
+
+/// Split a full name into (other names, surname).
+fn split_name(name: &str) -> (String, String) {
+    match name.rfind(' ') {
+        Some(idx) => (name[..idx].to_string(), name[idx + 1..].to_string()),
+        None => (String::new(), name.to_string()),
+    }
+}
+
+pub fn parse<R: Read>(mut input: R) -> Result<BltElection, Box<Error>> {
+    let mut contents = String::new();
+    input.read_to_string(&mut contents)?;
+
+    let mut lines = contents.lines().map(str::trim).filter(|l| !l.is_empty()).peekable();
+
+    let header: Vec<i64> = lines
+        .next()
+        .ok_or("empty BLT file")?
+        .split_whitespace()
+        .map(parse_num)
+        .collect::<Result<_, _>>()?;
+
+    if header.len() != 2 || header[0] < 1 || header[1] < 1 {
+        Err("BLT header should contain the number of candidates and seats")?;
+    }
+    let num_candidates = header[0] as usize;
+    let num_seats = header[1] as usize;
+
+    let mut withdrawn = vec![];
+    if lines.peek().map_or(false, |l| l.starts_with('-')) {
+        for token in lines.next().unwrap().split_whitespace() {
+            withdrawn.push(candidate_id(-parse_num(token)?, num_candidates)?);
+        }
+    }
+
+    let mut ballots = vec![];
+    loop {
+        let line = lines.next().ok_or("BLT file ended before the end of the ballots")?;
+        if line == "0" {
+            break;
+        }
+
+        // Skip the optional ballot ID.
+        let line = if line.starts_with('(') {
+            let end = line.find(')').ok_or("unterminated ballot ID")?;
+            &line[end + 1..]
+        } else {
+            line
+        };
+
+        match parse_ballot_line(line, num_candidates)? {
+            (weight, Ok(prefs)) => {
+                ballots.push(Ok(Ballot::multi(weight, prefs)));
+            }
+            (weight, Err(err)) => {
+                ballots.extend((0..weight).map(|_| Err(InvalidBallot(err))));
+            }
+        }
+    }
+
+    let mut candidates = CandidateMap::new();
+    for id in 0..num_candidates {
+        let name = unquote(lines.next().ok_or("BLT file is missing candidate names")?);
+        let (other_names, surname) = split_name(name);
+        candidates.insert(id as CandidateId, Candidate {
+            id: id as CandidateId,
+            surname: surname,
+            other_names: other_names,
+            group_name: String::new(),
+            party: String::new(),
+            state: String::new(),
+        });
+    }
+
+    let title = lines.next().map(unquote).unwrap_or("").to_string();
+
+    Ok(BltElection {
+        title: title,
+        candidates: candidates,
+        num_seats: num_seats,
+        withdrawn: withdrawn,
+        ballots: ballots,
+    })
+}
+
+fn write_ballot<W: Write>(
+    output: &mut W,
+    blt_nums: &HashMap<CandidateId, usize>,
+    weight: u32,
+    prefs: &[CandidateId],
+) -> Result<(), Box<Error>> {
+    write!(output, "{}", weight)?;
+    for id in prefs {
+        let num = blt_nums.get(id).ok_or_else(|| format!("unknown candidate ID: {}", id))?;
+        write!(output, " {}", num)?;
+    }
+    writeln!(output, " 0")?;
+    Ok(())
+}
+
+/// Write a set of formal ballots to BLT.
+///
+/// Candidates are numbered according to their position in `candidate_ids`. If `dedup` is set,
+/// identical ballots are combined into a single weighted line.
+pub fn write<'b, W, I>(
+    mut output: W,
+    title: &str,
+    candidates: &CandidateMap,
+    candidate_ids: &[CandidateId],
+    num_seats: usize,
+    ballots: I,
+    dedup: bool,
+) -> Result<(), Box<Error>>
+where
+    W: Write,
+    I: IntoIterator<Item = &'b Ballot>,
+{
+    let blt_nums: HashMap<CandidateId, usize> = candidate_ids
+        .iter()
+        .enumerate()
+        .map(|(idx, &id)| (id, idx + 1))
+        .collect();
+
+    writeln!(output, "{} {}", candidate_ids.len(), num_seats)?;
+
+    if dedup {
+        let mut weights: BTreeMap<&[CandidateId], u32> = BTreeMap::new();
+        for ballot in ballots {
+            *weights.entry(&ballot.prefs).or_insert(0) += ballot.weight();
+        }
+        for (prefs, weight) in weights {
+            write_ballot(&mut output, &blt_nums, weight, prefs)?;
+        }
+    } else {
+        for ballot in ballots {
+            write_ballot(&mut output, &blt_nums, ballot.weight(), &ballot.prefs)?;
+        }
+    }
+
+    writeln!(output, "0")?;
+
+    for id in candidate_ids {
+        let c = &candidates[id];
+        let name = format!("{} {}", c.other_names, c.surname);
+        writeln!(output, "\"{}\"", name.trim().replace('"', "'"))?;
+    }
+    writeln!(output, "\"{}\"", title.replace('"', "'"))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use voting::decide_election;
+
+    const EXAMPLE: &str = r#"
+        4 2
+        -4
+        3 1 2 0
+        4 2 1 0
+        (x1) 2 3 1 0
+        1 1 1 0
+        1 0
+        0
+        "Alice Smith"
+        "Bob Jones"
+        "Carol"
+        "Dave"
+        "Example"
+    "#;
+
+    #[test]
+    fn parse_example() {
+        let election = parse(EXAMPLE.as_bytes()).unwrap();
+        assert_eq!(election.num_seats, 2);
+        assert_eq!(election.withdrawn, vec![3]);
+        assert_eq!(election.title, "Example");
+        assert_eq!(election.candidates[&0].surname, "Smith");
+        assert_eq!(election.candidates[&2].surname, "Carol");
+
+        // One ballot per line, carrying the line's weight.
+        assert_eq!(election.ballots.len(), 5);
+        let num_votes: u32 = election.ballots
+            .iter()
+            .map(|b| b.as_ref().map(|b| b.weight()).unwrap_or(0))
+            .sum();
+        assert_eq!(num_votes, 10);
+
+        let result = decide_election(
+            &election.candidates,
+            &election.withdrawn,
+            election.ballots,
+            election.num_seats,
+        ).unwrap();
+        let mut elected: Vec<_> = result.senators.iter().map(|&(ref c, _)| c.id).collect();
+        elected.sort();
+        assert_eq!(elected, vec![0, 1]);
+        assert_eq!(result.stats.num_invalid_votes(), 1);
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let election = parse(EXAMPLE.as_bytes()).unwrap();
+        let ballots: Vec<Ballot> = election.ballots.into_iter().filter_map(Result::ok).collect();
+        let ids = (0..4).collect::<Vec<_>>();
+
+        let mut output = vec![];
+        write(&mut output, "Example", &election.candidates, &ids, 2, &ballots, true).unwrap();
+
+        let reparsed = parse(&output[..]).unwrap();
+        assert_eq!(reparsed.candidates[&0].surname, "Smith");
+        let mut reparsed_ballots: Vec<Ballot> =
+            reparsed.ballots.into_iter().filter_map(Result::ok).collect();
+        let total = |bs: &[Ballot]| bs.iter().map(|b| b.weight()).sum::<u32>();
+        assert_eq!(total(&reparsed_ballots), total(&ballots));
+        reparsed_ballots.dedup_by(|a, b| a.prefs == b.prefs);
+        assert_eq!(reparsed_ballots.len(), 4);
+    }
+}
@@ -3,6 +3,9 @@ use super::prelude::*;
 /// Below the line voting map. Maps (batch, paper) pairs to preferences.
 pub type BelowTheLine = HashMap<(u32, u32), PrefMap>;
 
+/// Maximum number of breaks in the sequence of preferences for a ballot to be saved.
+const MAX_SEQUENCE_BREAKS: usize = 3;
+
 #[derive(Deserialize, Debug)]
 struct BTLRow {
     candidate_id: CandidateId,
@@ -11,36 +14,203 @@ struct BTLRow {
     paper: u32,
 }
 
-pub fn parse<R: Read>(input: R) -> Result<BelowTheLine, Box<Error>> {
-    let mut btl_votes = HashMap::new();
-    let mut invalid_votes = HashSet::new();
+/// The markings on a single below the line ballot, before any formality checks.
+#[derive(Default, Debug)]
+struct RawBallot {
+    /// Map from preference numbers to the candidates marked with that number.
+    marks: BTreeMap<u32, Vec<CandidateId>>,
+    /// Candidates whose squares were left blank.
+    unmarked: Vec<CandidateId>,
+}
+
+/// Savings provisions that allow a ballot with errors to be counted (2013 rules).
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SavingsRule {
+    /// All squares but one were numbered in sequence. The blank square is taken to be
+    /// the voter's last preference.
+    LastSquareBlank,
+    /// The sequence of preferences has a small number of breaks (repeated or missing numbers).
+    /// Preferences are counted up to the first break.
+    SequenceErrors,
+}
+
+/// Counts of below the line ballots by how they were treated.
+#[derive(Default, Debug, Clone)]
+pub struct Savings {
+    /// Ballots that were fully formal without any savings provision.
+    pub formal: u32,
+    /// Ballots saved by `SavingsRule::LastSquareBlank`.
+    pub last_square_blank: u32,
+    /// Ballots saved by `SavingsRule::SequenceErrors`.
+    pub sequence_errors: u32,
+    /// Ballots that were informal even with the savings provisions.
+    pub informal: u32,
+}
+
+impl Savings {
+    fn record(&mut self, result: &Result<(PrefMap, Option<SavingsRule>), ()>) {
+        match *result {
+            Ok((_, None)) => self.formal += 1,
+            Ok((_, Some(SavingsRule::LastSquareBlank))) => self.last_square_blank += 1,
+            Ok((_, Some(SavingsRule::SequenceErrors))) => self.sequence_errors += 1,
+            Err(()) => self.informal += 1,
+        }
+    }
+
+    pub fn num_saved(&self) -> u32 {
+        self.last_square_blank + self.sequence_errors
+    }
+}
+
+/// Parse a state's below the line votes, applying the savings provisions of Section 270.
+///
+/// `num_candidates` is the number of squares below the line on the ballot paper.
+pub fn parse<R: Read>(
+    input: R,
+    num_candidates: usize,
+) -> Result<(BelowTheLine, Savings), Box<Error>> {
+    let mut raw_ballots: HashMap<(u32, u32), RawBallot> = HashMap::new();
     let mut reader = ::csv::Reader::from_reader(input);
 
     for raw_row in reader.deserialize::<BTLRow>() {
         let row = raw_row?;
-        let vote_id = (row.batch, row.paper);
+        let ballot = raw_ballots.entry((row.batch, row.paper)).or_insert_with(RawBallot::default);
         match row.preference {
             Some(pref) => {
-                let voter_prefs = btl_votes.entry(vote_id).or_insert_with(BTreeMap::new);
-                let prev = voter_prefs.insert(pref, row.candidate_id);
-                // Can't assign a single preference to more than one candidate!
-                // NOTE: this makes loads of NSW BTL ballots invalid... around 15%. Weird.
-                if prev.is_some() {
-                    invalid_votes.insert(vote_id);
-                }
+                ballot.marks.entry(pref).or_insert_with(Vec::new).push(row.candidate_id);
             }
             None => {
-                invalid_votes.insert(vote_id);
+                ballot.unmarked.push(row.candidate_id);
             }
         }
     }
 
-    // Remove invalid votes.
-    println!("Invalid BTL votes: {}", invalid_votes.len());
-    for vote_id in invalid_votes {
-        btl_votes.remove(&vote_id);
+    let mut btl_votes = HashMap::new();
+    let mut savings = Savings::default();
+
+    for (vote_id, ballot) in raw_ballots {
+        let result = apply_savings(ballot, num_candidates);
+        savings.record(&result);
+        if let Ok((pref_map, _)) = result {
+            btl_votes.insert(vote_id, pref_map);
+        }
+    }
+
+    Ok((btl_votes, savings))
+}
+
+/// Determine the formal preferences of a ballot, and which savings rule (if any) was needed.
+fn apply_savings(
+    ballot: RawBallot,
+    num_candidates: usize,
+) -> Result<(PrefMap, Option<SavingsRule>), ()> {
+    let RawBallot { marks, unmarked } = ballot;
+
+    // Length of the unbroken sequence of single preferences starting from 1.
+    let sequence_len = (1..)
+        .take_while(|pref| marks.get(pref).map_or(false, |c| c.len() == 1))
+        .count();
+
+    let mut pref_map: PrefMap = (1..)
+        .zip(marks.values())
+        .take(sequence_len)
+        .map(|(pref, cands)| (pref, cands[0]))
+        .collect();
+
+    if sequence_len == num_candidates {
+        return Ok((pref_map, None));
     }
-    println!("Valid BTL votes: {}", btl_votes.len());
 
-    Ok(btl_votes)
+    let num_marked: usize = marks.values().map(|c| c.len()).sum();
+
+    if sequence_len + 1 == num_candidates && num_marked == sequence_len && unmarked.len() == 1 {
+        pref_map.insert(num_candidates as u32, unmarked[0]);
+        return Ok((pref_map, Some(SavingsRule::LastSquareBlank)));
+    }
+
+    // Count breaks in the sequence: numbers up to the highest used that are missing or used
+    // more than once. Blank squares after the highest number aren't breaks.
+    let highest = marks.keys().cloned().max().unwrap_or(0).min(num_candidates as u32);
+    let num_breaks = (1..highest + 1)
+        .filter(|pref| marks.get(pref).map_or(true, |c| c.len() != 1))
+        .count();
+
+    // At least 90% of the squares must be numbered in sequence, with 1 marked exactly once.
+    let min_sequence = (num_candidates * 9 + 9) / 10;
+
+    if sequence_len >= 1 && sequence_len >= min_sequence && num_breaks <= MAX_SEQUENCE_BREAKS {
+        return Ok((pref_map, Some(SavingsRule::SequenceErrors)));
+    }
+
+    Err(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn raw_ballot(prefs: &[(u32, CandidateId)], unmarked: &[CandidateId]) -> RawBallot {
+        let mut ballot = RawBallot::default();
+        for &(pref, cand) in prefs {
+            ballot.marks.entry(pref).or_insert_with(Vec::new).push(cand);
+        }
+        ballot.unmarked = unmarked.to_vec();
+        ballot
+    }
+
+    #[test]
+    fn savings_rules() {
+        let all: Vec<_> = (1..21).map(|i| (i, i as CandidateId)).collect();
+
+        let formal = apply_savings(raw_ballot(&all, &[]), 20).unwrap();
+        assert_eq!(formal.0.len(), 20);
+        assert_eq!(formal.1, None);
+
+        let last_blank = apply_savings(raw_ballot(&all[..19], &[20]), 20).unwrap();
+        assert_eq!(last_blank.0[&20], 20);
+        assert_eq!(last_blank.1, Some(SavingsRule::LastSquareBlank));
+
+        // Repeated 19, so preferences stop at 18.
+        let mut repeated = all.clone();
+        repeated[19].0 = 19;
+        let seq = apply_savings(raw_ballot(&repeated, &[]), 20).unwrap();
+        assert_eq!(seq.0.len(), 18);
+        assert_eq!(seq.1, Some(SavingsRule::SequenceErrors));
+
+        // 90% numbered in sequence, with the last two squares blank.
+        let unfinished = apply_savings(raw_ballot(&all[..18], &[19, 20]), 20).unwrap();
+        assert_eq!(unfinished.0.len(), 18);
+        assert_eq!(unfinished.1, Some(SavingsRule::SequenceErrors));
+
+        // Fewer than 90% numbered.
+        assert!(apply_savings(raw_ballot(&all[..17], &[18, 19, 20]), 20).is_err());
+
+        // Repeated 2 is too early in the sequence to be saved.
+        let mut early = all.clone();
+        early[2].0 = 2;
+        assert!(apply_savings(raw_ballot(&early, &[]), 20).is_err());
+    }
+
+    #[test]
+    fn savings_with_blank_squares() {
+        let all: Vec<_> = (1..111).map(|i| (i, i as CandidateId)).collect();
+        let blank: Vec<CandidateId> = (101..111).collect();
+
+        // Numbered from 1 to 100 of 110, with the last 10 squares blank.
+        let unfinished = apply_savings(raw_ballot(&all[..100], &blank), 110).unwrap();
+        assert_eq!(unfinished.0.len(), 100);
+        assert_eq!(unfinished.1, Some(SavingsRule::SequenceErrors));
+
+        // Repeated 102 late in the sequence: preferences stop at 101.
+        let mut late = all.clone();
+        late[102].0 = 102;
+        let seq = apply_savings(raw_ballot(&late, &[]), 110).unwrap();
+        assert_eq!(seq.0.len(), 101);
+        assert_eq!(seq.1, Some(SavingsRule::SequenceErrors));
+
+        // Repeated 55 in the middle of the sequence leaves too few preferences to save.
+        let mut middle = all.clone();
+        middle[55].0 = 55;
+        assert!(apply_savings(raw_ballot(&middle, &[]), 110).is_err());
+    }
 }
@@ -0,0 +1,49 @@
+//! Parser for a simple candidate list, used for state elections with a single electorate.
+//!
+//! The list is a CSV file with columns `group,ballot_position,surname,given_names,party`,
+//! with `UG` as the group of ungrouped candidates.
+
+use csv;
+
+use super::prelude::*;
+
+#[derive(Deserialize, Debug)]
+struct CandidateRow {
+    group: String,
+    ballot_position: u32,
+    surname: String,
+    given_names: String,
+    party: String,
+}
+
+/// Parse the candidate list, returning candidates in ballot paper order.
+///
+/// IDs are assigned in ballot paper order, starting from 0, and every candidate is given the
+/// state `state`.
+pub fn parse<R: Read>(input: R, state: &str) -> Result<Vec<Candidate>, Box<Error>> {
+    let mut reader = csv::Reader::from_reader(input);
+
+    let mut rows = vec![];
+    for raw_row in reader.deserialize::<CandidateRow>() {
+        rows.push(raw_row?);
+    }
+
+    rows.sort_by(|r1, r2| {
+        (ticket_order(&r1.group), r1.ballot_position)
+            .cmp(&(ticket_order(&r2.group), r2.ballot_position))
+    });
+
+    let candidates = rows.into_iter()
+        .enumerate()
+        .map(|(id, row)| Candidate {
+            id: id as CandidateId,
+            surname: row.surname,
+            other_names: row.given_names,
+            group_name: row.group,
+            party: row.party,
+            state: state.to_string(),
+        })
+        .collect();
+
+    Ok(candidates)
+}
@@ -29,6 +29,11 @@ struct CandidateRow {
     contact_email: String,
 }
 
+/// Parse the candidates file, returning candidates in ballot paper order.
+///
+/// Candidate IDs are assigned in file order, but the list is sorted by state, ticket and
+/// ballot position so that it matches the columns of the preferences file regardless of
+/// how the AEC ordered the rows.
 pub fn parse<R: Read>(input: R) -> Result<Vec<Candidate>, Box<Error>> {
     let mut result = vec![];
     let mut reader = ::csv::Reader::from_reader(input);
@@ -38,15 +43,21 @@ pub fn parse<R: Read>(input: R) -> Result<Vec<Candidate>, Box<Error>> {
         if row.nom_ty != "S" {
             continue;
         }
-        result.push(Candidate {
+        let position = row.ballot_position;
+        result.push((position, Candidate {
             id: id as CandidateId,
             surname: row.surname,
             other_names: row.ballot_given_nm,
             group_name: row.ticket,
             party: row.party_ballot_nm,
             state: row.state_ab,
-        });
+        }));
     }
 
-    Ok(result)
+    result.sort_by(|&(pos1, ref c1), &(pos2, ref c2)| {
+        (&c1.state, ticket_order(&c1.group_name), pos1)
+            .cmp(&(&c2.state, ticket_order(&c2.group_name), pos2))
+    });
+
+    Ok(result.into_iter().map(|(_, c)| c).collect())
 }
@@ -1,7 +1,12 @@
+use rand::Rng;
+
 use super::prelude::*;
+use ballot::Ballot;
 
 /// Group voting ticket description. Maps states to ticket names to preference lists.
-pub type GVT = HashMap<String, HashMap<String, Vec<CandidateId>>>;
+///
+/// Groups may lodge up to three tickets, which are stored in order of ticket number.
+pub type GVT = HashMap<String, HashMap<String, Vec<Vec<CandidateId>>>>;
 
 /// Holy moley.
 #[derive(Deserialize, Debug)]
@@ -23,7 +28,7 @@ struct GVTRow {
 
 // NOTE: This is a tad slow, but it beats mucking around with manual row groupings.
 pub fn parse<R: Read>(input: R) -> Result<GVT, Box<Error>> {
-    let mut data: HashMap<String, HashMap<String, PrefMap>> = HashMap::new();
+    let mut data: HashMap<String, HashMap<String, BTreeMap<u32, PrefMap>>> = HashMap::new();
 
     let mut reader = ::csv::Reader::from_reader(input);
 
@@ -32,6 +37,8 @@ pub fn parse<R: Read>(input: R) -> Result<GVT, Box<Error>> {
         let ticket_map = data.entry(row.state).or_insert_with(HashMap::new);
         let pref_map = ticket_map
             .entry(row.owner_ticket)
+            .or_insert_with(BTreeMap::new)
+            .entry(row.ticket_num)
             .or_insert_with(BTreeMap::new);
         pref_map.insert(row.preference, row.candidate_id);
     }
@@ -40,9 +47,85 @@ pub fn parse<R: Read>(input: R) -> Result<GVT, Box<Error>> {
     let mut result = HashMap::new();
     for (state, ticket_map) in data {
         let new_ticket_map = result.entry(state).or_insert_with(HashMap::new);
-        for (ticket, pref_map) in ticket_map {
-            new_ticket_map.insert(ticket, flatten_pref_map(pref_map));
+        for (ticket, pref_maps) in ticket_map {
+            let tickets = pref_maps
+                .into_iter()
+                .map(|(_, pref_map)| flatten_pref_map(pref_map))
+                .collect();
+            new_ticket_map.insert(ticket, tickets);
         }
     }
     Ok(result)
 }
+
+/// Divide a group's ticket votes between its tickets, as per Section 272 of the Electoral Act.
+///
+/// Each ticket receives an equal share, and any remaining votes are allotted one each to
+/// tickets chosen by lot.
+pub fn split_ticket_votes<R: Rng>(votes: u32, num_tickets: usize, rng: &mut R) -> Vec<u32> {
+    if num_tickets == 0 {
+        return vec![];
+    }
+    let share = votes / num_tickets as u32;
+    let remainder = (votes % num_tickets as u32) as usize;
+
+    let mut lot: Vec<usize> = (0..num_tickets).collect();
+    rng.shuffle(&mut lot);
+
+    let mut split = vec![share; num_tickets];
+    for &idx in &lot[..remainder] {
+        split[idx] += 1;
+    }
+    split
+}
+
+/// Create the ballots cast by following group voting tickets, for a single state or region.
+///
+/// `tickets` maps group names to their tickets, and `ticket_votes` maps group names to the
+/// number of votes cast for the group above the line. Groups are visited in order of name, so
+/// the votes are split the same way for a given seed.
+pub fn ticket_ballots<R: Rng>(
+    tickets: &HashMap<String, Vec<Vec<CandidateId>>>,
+    ticket_votes: &HashMap<String, u32>,
+    rng: &mut R,
+) -> Result<Vec<IOBallot>, Box<Error>> {
+    let mut ballots = vec![];
+    let mut groups: Vec<(&String, &u32)> = ticket_votes.iter().collect();
+    groups.sort();
+    for (group, &vote_count) in groups {
+        // If the vote count is 0, then we can safely skip adding this bit of GVT usage.
+        // The AEC files are strange in that some groups are included in the GVT usage with
+        // with a count of 0, but absent are from the actual GVT description.
+        if vote_count == 0 {
+            continue;
+        }
+        let group_tickets = tickets
+            .get(group)
+            .ok_or_else(|| format!("no voting ticket for group {}", group))?;
+
+        // Groups with more than one ticket have their votes divided between them (Section 272).
+        let split = split_ticket_votes(vote_count, group_tickets.len(), rng);
+        for (ticket, votes) in group_tickets.iter().zip(split).filter(|&(_, v)| v != 0) {
+            ballots.push(Ok(Ballot::multi(votes, ticket.clone())));
+        }
+    }
+    Ok(ballots)
+}
+
+#[cfg(test)]
+mod test {
+    use super::split_ticket_votes;
+    use rand::thread_rng;
+
+    #[test]
+    fn split_votes() {
+        let mut rng = thread_rng();
+        assert_eq!(split_ticket_votes(10, 1, &mut rng), vec![10]);
+        assert_eq!(split_ticket_votes(10, 2, &mut rng), vec![5, 5]);
+
+        let split = split_ticket_votes(11, 3, &mut rng);
+        assert_eq!(split.iter().sum::<u32>(), 11);
+        assert_eq!(split.iter().filter(|&&v| v == 4).count(), 2);
+        assert_eq!(split.iter().filter(|&&v| v == 3).count(), 1);
+    }
+}
@@ -25,7 +25,7 @@ pub fn parse<R: Read>(input: R) -> Result<GVTUsage, Box<Error>> {
         let row = raw_row?;
         let ticket_map = gvt_usage.entry(row.state).or_insert_with(HashMap::new);
         // Skip ungrouped candidates with 0 vote.
-        if &row.ticket == "UG" {
+        if row.ticket == UNGROUPED {
             continue;
         }
         let prev = ticket_map.insert(row.ticket, row.ticket_votes);
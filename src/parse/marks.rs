@@ -0,0 +1,49 @@
+//! Helper for preference files with one row per mark on a ballot paper.
+
+/// The marks on the ballot paper currently being read, as columns of a preference string.
+///
+/// Rows for each ballot paper must be consecutive.
+pub struct PaperMarks {
+    paper_id: Option<String>,
+    columns: Vec<String>,
+}
+
+impl PaperMarks {
+    pub fn new(num_columns: usize) -> PaperMarks {
+        PaperMarks {
+            paper_id: None,
+            columns: vec![String::new(); num_columns],
+        }
+    }
+
+    /// Move on to the ballot paper with ID `paper_id`.
+    ///
+    /// If this is a different paper to the current one, the preference string for the current
+    /// paper is returned, in the format expected by `parse_ballot_str`.
+    pub fn next_paper(&mut self, paper_id: &str) -> Option<String> {
+        if self.paper_id.as_ref().map_or(false, |id| id == paper_id) {
+            return None;
+        }
+        let prev = self.paper_id.take().map(|_| self.columns.join(","));
+        for column in &mut self.columns {
+            column.clear();
+        }
+        self.paper_id = Some(paper_id.to_string());
+        prev
+    }
+
+    /// Record a mark on the current paper.
+    pub fn mark(&mut self, column: usize, pref: &str) {
+        self.columns[column] = pref.trim().to_string();
+    }
+
+    pub fn paper_id(&self) -> &str {
+        self.paper_id.as_ref().map_or("", |id| id)
+    }
+
+    /// Finish reading, returning the preference string of the last paper, if any.
+    pub fn finish(self) -> Option<String> {
+        let columns = self.columns;
+        self.paper_id.map(|_| columns.join(","))
+    }
+}
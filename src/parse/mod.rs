@@ -10,3 +10,13 @@ pub mod prefs2016;
 pub mod gvt2013;
 pub mod gvt_usage2013;
 pub mod btl2013;
+pub mod blt;
+mod marks;
+pub mod nsw_lc;
+pub mod vic_lc;
+pub mod candidate_list;
+pub mod paper_rows;
+pub mod sa_lc;
+pub mod wa_lc;
+pub mod act;
+pub mod tas;
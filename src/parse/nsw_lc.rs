@@ -0,0 +1,228 @@
+//! Parsers for NSW Legislative Council data published by the NSW Electoral Commission.
+//!
+//! The preference data is a tab-separated file with one row per mark on a ballot paper.
+//! The columns used are:
+//!
+//! * `VCBallotPaperID`: identifies the ballot paper, whose rows are consecutive.
+//! * `GroupCode`: the group of the square marked (`UG` for ungrouped candidates).
+//! * `CandidateName`: the candidate marked, blank for marks above the line.
+//! * `PreferenceNumber`: the number written in the square.
+//! * `Type`: `ATL` or `BTL`.
+//!
+//! Other columns are ignored.
+//!
+//! The candidate list is a CSV file with columns `GroupCode`, `BallotPosition`,
+//! `CandidateName` and `PartyName`, with names written as `SURNAME Other Names`.
+
+use csv;
+
+use super::prelude::*;
+use group::Group;
+use diagnostics::RowError;
+use super::marks::PaperMarks;
+
+/// State name used for NSW candidates.
+pub const NSW: &str = "NSW";
+
+#[derive(Deserialize, Debug)]
+struct CandidateRow {
+    #[serde(rename = "GroupCode")]
+    group_code: String,
+    #[serde(rename = "BallotPosition")]
+    ballot_position: u32,
+    #[serde(rename = "CandidateName")]
+    candidate_name: String,
+    #[serde(rename = "PartyName")]
+    party_name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct PrefRow {
+    #[serde(rename = "VCBallotPaperID")]
+    ballot_paper_id: String,
+    #[serde(rename = "GroupCode")]
+    group_code: String,
+    #[serde(rename = "CandidateName")]
+    candidate_name: String,
+    #[serde(rename = "PreferenceNumber")]
+    preference_number: String,
+    #[serde(rename = "Type")]
+    mark_type: String,
+}
+
+/// Split a name like `VAN DER BERG Anna Maria` into other names and surname.
+fn split_name(name: &str) -> (String, String) {
+    let words: Vec<&str> = name.split_whitespace().collect();
+    let num_surname = words
+        .iter()
+        .take_while(|w| !w.chars().any(char::is_lowercase))
+        .count()
+        .max(1)
+        .min(words.len());
+    (words[num_surname..].join(" "), words[..num_surname].join(" "))
+}
+
+/// Parse the candidate list, returning candidates in ballot paper order.
+///
+/// IDs are assigned in ballot paper order, starting from 0.
+pub fn parse_candidates<R: Read>(input: R) -> Result<Vec<Candidate>, Box<Error>> {
+    let mut reader = csv::Reader::from_reader(input);
+
+    let mut rows = vec![];
+    for raw_row in reader.deserialize::<CandidateRow>() {
+        rows.push(raw_row?);
+    }
+
+    rows.sort_by(|r1, r2| {
+        (ticket_order(&r1.group_code), r1.ballot_position)
+            .cmp(&(ticket_order(&r2.group_code), r2.ballot_position))
+    });
+
+    let candidates = rows.into_iter()
+        .enumerate()
+        .map(|(id, row)| {
+            let (other_names, surname) = split_name(&row.candidate_name);
+            Candidate {
+                id: id as CandidateId,
+                surname: surname,
+                other_names: other_names,
+                group_name: row.group_code,
+                party: row.party_name,
+                state: NSW.to_string(),
+            }
+        })
+        .collect();
+
+    Ok(candidates)
+}
+
+/// Full name of a candidate as it appears in the preference data.
+fn full_name(c: &Candidate) -> String {
+    format!("{} {}", c.surname, c.other_names).trim().to_string()
+}
+
+/// Parse the NSWEC preference data into ballots.
+///
+/// `candidates` must be in ballot paper order, as returned by `parse_candidates`.
+pub fn parse_preferences<R: Read>(
+    input: R,
+    file_name: &str,
+    candidates: &[Candidate],
+    groups: &[Group],
+    constraints: &Constraints,
+) -> Vec<IOBallot> {
+    let candidate_ids: Vec<CandidateId> = candidates.iter().map(|c| c.id).collect();
+    let group_columns: HashMap<&str, usize> = groups
+        .iter()
+        .enumerate()
+        .map(|(idx, g)| (&g.name[..], idx))
+        .collect();
+    let candidate_columns: HashMap<String, usize> = candidates
+        .iter()
+        .enumerate()
+        .map(|(idx, c)| (full_name(c), groups.len() + idx))
+        .collect();
+
+    let num_columns = groups.len() + candidates.len();
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .from_reader(input);
+    let headers = match reader.headers() {
+        Ok(h) => h.clone(),
+        Err(e) => return vec![Err(InputError(Box::new(RowError::new(file_name, None, e))))],
+    };
+
+    let to_ballot = |prefs: String| parse_ballot_str(&prefs, groups, &candidate_ids, constraints);
+
+    let mut ballots = vec![];
+    let mut paper = PaperMarks::new(num_columns);
+
+    for raw_row in reader.records() {
+        let record = match raw_row {
+            Ok(r) => r,
+            Err(e) => {
+                ballots.push(Err(InputError(Box::new(RowError::new(file_name, None, e)))));
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line());
+        let row_error = |e: String| Err(InputError(Box::new(RowError::new(file_name, line, e))));
+
+        let row: PrefRow = match record.deserialize(Some(&headers)) {
+            Ok(r) => r,
+            Err(e) => {
+                ballots.push(row_error(e.to_string()));
+                continue;
+            }
+        };
+
+        if let Some(prefs) = paper.next_paper(&row.ballot_paper_id) {
+            ballots.push(to_ballot(prefs));
+        }
+
+        let column = match &row.mark_type[..] {
+            "ATL" => group_columns.get(&row.group_code[..]).cloned().ok_or_else(|| {
+                format!("unknown group {:?} on ballot paper {}", row.group_code, paper.paper_id())
+            }),
+            "BTL" => candidate_columns.get(row.candidate_name.trim()).cloned().ok_or_else(|| {
+                format!("unknown candidate {:?} on ballot paper {}", row.candidate_name, paper.paper_id())
+            }),
+            // Ballot papers without any marks have a single row with no type.
+            "" => continue,
+            other => Err(format!("unknown mark type {:?}", other)),
+        };
+
+        match column {
+            Ok(col) => paper.mark(col, &row.preference_number),
+            Err(e) => ballots.push(row_error(e)),
+        }
+    }
+
+    if let Some(prefs) = paper.finish() {
+        ballots.push(to_ballot(prefs));
+    }
+
+    ballots
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use group::get_group_list;
+    use ballot::Ballot;
+
+    const CANDIDATES: &str = "\
+GroupCode,BallotPosition,CandidateName,PartyName
+B,1,JONES Bob,Blue Party
+A,2,SMITH Jane,Red Party
+A,1,VAN DER BERG Anna Maria,Red Party
+UG,1,LONER Ian,Independent
+";
+
+    #[test]
+    fn parse_example() {
+        let candidates = parse_candidates(CANDIDATES.as_bytes()).unwrap();
+        assert_eq!(candidates[0].surname, "VAN DER BERG");
+        assert_eq!(candidates[0].other_names, "Anna Maria");
+        assert_eq!(candidates[3].group_name, "UG");
+
+        let groups = get_group_list(&candidates, NSW);
+        assert_eq!(groups.len(), 2);
+
+        let prefs = "\
+VCBallotPaperID\tGroupCode\tCandidateName\tPreferenceNumber\tType
+1\tB\t\t1\tATL
+1\tA\t\t2\tATL
+2\tUG\tLONER Ian\t1\tBTL
+2\tA\tSMITH Jane\t2\tBTL
+3\t\t\t\t
+";
+        let constraints = Constraints { choice: PreferBelow, counts: vec![MinAbove(1), MinBelow(2)] };
+        let ballots = parse_preferences(prefs.as_bytes(), "prefs.txt", &candidates, &groups, &constraints);
+        assert_eq!(ballots.len(), 3);
+        assert_eq!(*ballots[0].as_ref().unwrap(), Ballot::single_above(vec![2, 0, 1]));
+        assert_eq!(*ballots[1].as_ref().unwrap(), Ballot::single(vec![3, 1]));
+        assert!(ballots[2].is_err());
+    }
+}
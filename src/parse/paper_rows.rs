@@ -0,0 +1,69 @@
+//! Parser for preference files with one row per ballot paper.
+//!
+//! The first two columns are the batch and paper numbers, followed by one column per square on
+//! the ballot paper: any group squares above the line, then the candidate squares, in ballot
+//! paper order. Column headers other than the count of columns are not checked.
+
+use csv;
+
+use super::prelude::*;
+use super::prefs2016::{parse_rows_parallel, DEFAULT_CHUNK_SIZE};
+use group::Group;
+use diagnostics::RowError;
+
+/// Number of columns before the preferences in the preference data.
+const NUM_INFO_COLUMNS: usize = 2;
+
+fn parse_ballot(
+    raw_row: csv::Result<csv::StringRecord>,
+    file_name: &str,
+    groups: &[Group],
+    candidates: &[CandidateId],
+    constraints: &Constraints,
+) -> IOBallot {
+    let row = match raw_row {
+        Ok(row) => row,
+        Err(e) => return Err(InputError(Box::new(RowError::new(file_name, None, e)))),
+    };
+    let prefs = row.iter().skip(NUM_INFO_COLUMNS).collect::<Vec<_>>().join(",");
+    parse_ballot_str(&prefs, groups, candidates, constraints).map_err(|e| match e {
+        InputError(e) => {
+            let line = row.position().map(|p| p.line());
+            let mut err = RowError::new(file_name, line, e);
+            if let (Some(batch), Some(paper)) = (row.get(0), row.get(1)) {
+                err = err.with_ballot_id(batch, paper);
+            }
+            InputError(Box::new(err))
+        }
+        e => e,
+    })
+}
+
+/// Parse a preference file into ballots.
+///
+/// Pass no `groups` for ballot papers without squares above the line.
+pub fn parse<R: Read>(
+    input: R,
+    file_name: &str,
+    groups: &[Group],
+    candidate_ids: &[CandidateId],
+    constraints: &Constraints,
+) -> Result<Vec<IOBallot>, Box<Error>> {
+    let mut reader = csv::Reader::from_reader(input);
+
+    let num_columns = reader.headers()?.len();
+    if num_columns != NUM_INFO_COLUMNS + groups.len() + candidate_ids.len() {
+        Err(format!(
+            "{} has {} columns, expected {} for {} groups and {} candidates",
+            file_name,
+            num_columns,
+            NUM_INFO_COLUMNS + groups.len() + candidate_ids.len(),
+            groups.len(),
+            candidate_ids.len()
+        ))?;
+    }
+
+    Ok(parse_rows_parallel(&mut reader, DEFAULT_CHUNK_SIZE, |row| {
+        parse_ballot(row, file_name, groups, candidate_ids, constraints)
+    }))
+}
@@ -1,7 +1,10 @@
 use csv;
+use rayon::prelude::*;
 use group::Group;
+use diagnostics::RowError;
 use super::prelude::*;
 
+/// A row of a 2016 preferences file, with all preferences in a single column.
 #[derive(Deserialize, Debug)]
 pub struct PrefRow {
     #[serde(rename = "ElectorateNm")]
@@ -18,17 +21,170 @@ pub struct PrefRow {
     preferences: String,
 }
 
+/// Parse a single row of a preferences file, annotating any input errors with the file name
+/// and position of the row.
 pub fn parse_single_ballot(
-    raw_row: csv::Result<PrefRow>,
+    raw_row: csv::Result<csv::StringRecord>,
+    headers: Option<&csv::StringRecord>,
+    file_name: &str,
     groups: &[Group],
     candidates: &[CandidateId],
     constraints: &Constraints,
-    experiment_num: usize,
 ) -> IOBallot {
-    match raw_row {
-        Ok(row) => parse_ballot_str(&row.preferences, groups, candidates, constraints, experiment_num),
-        Err(e) => Err(InputError(From::from(e))),
+    let record = match raw_row {
+        Ok(record) => record,
+        // CSV errors describe their own position.
+        Err(e) => return Err(InputError(Box::new(RowError::new(file_name, None, e)))),
+    };
+    let line = record.position().map(|p| p.line());
+    let row: PrefRow = match record.deserialize(headers) {
+        Ok(row) => row,
+        Err(e) => return Err(InputError(Box::new(RowError::new(file_name, line, e)))),
+    };
+    parse_ballot_str(&row.preferences, groups, candidates, constraints)
+        .map_err(|e| match e {
+            InputError(e) => {
+                let err = RowError::new(file_name, line, e)
+                    .with_ballot_id(&row.batch_num, &row.paper_num);
+                InputError(Box::new(err))
+            }
+            e => e,
+        })
+}
+
+/// Number of columns before the preferences in files with one column per preference.
+const NUM_INFO_COLUMNS: usize = 6;
+
+/// Check whether a preferences file has one labelled column per preference (as published from
+/// 2019 onwards), rather than a single `Preferences` column (as published in 2016).
+pub fn has_labelled_columns(header: &csv::StringRecord) -> bool {
+    header.iter().all(|h| h != "Preferences")
+}
+
+/// Determine the candidate for each below the line column, from the column labels.
+///
+/// Labels have the form `A:Party Name` above the line and `A:SURNAME Given Names` below the
+/// line, with ungrouped candidates labelled `UG:SURNAME Given Names`. The above the line labels
+/// are checked against the group list, and every candidate must have exactly one column.
+pub fn candidate_columns(
+    header: &csv::StringRecord,
+    groups: &[Group],
+    candidates: &CandidateMap,
+) -> Result<Vec<CandidateId>, Box<Error>> {
+    let labels: Vec<(&str, &str)> = header
+        .iter()
+        .skip(NUM_INFO_COLUMNS)
+        .map(|label| {
+            let mut parts = label.splitn(2, ':');
+            (parts.next().unwrap_or(""), parts.next().unwrap_or(""))
+        })
+        .collect();
+
+    if labels.len() != groups.len() + candidates.len() {
+        Err(format!(
+            "preferences file has {} columns, expected {} groups and {} candidates",
+            labels.len(),
+            groups.len(),
+            candidates.len()
+        ))?;
+    }
+
+    let (group_labels, candidate_labels) = labels.split_at(groups.len());
+
+    for (&(ticket, _), group) in group_labels.iter().zip(groups) {
+        if ticket == UNGROUPED || ticket != group.name {
+            Err(format!(
+                "above the line column for ticket {} doesn't match group {}",
+                ticket,
+                group.name
+            ))?;
+        }
+    }
+
+    let mut columns = vec![];
+    for &(ticket, name) in candidate_labels {
+        let name = normalise_name(name);
+        let matching: Vec<CandidateId> = candidates
+            .values()
+            .filter(|c| {
+                c.group_name == ticket &&
+                    normalise_name(&format!("{} {}", c.surname, c.other_names)) == name
+            })
+            .map(|c| c.id)
+            .collect();
+
+        match matching.len() {
+            1 if !columns.contains(&matching[0]) => columns.push(matching[0]),
+            1 => Err(format!("duplicate column for candidate {}:{}", ticket, name))?,
+            0 => Err(format!("no candidate matches column {}:{}", ticket, name))?,
+            _ => Err(format!("column {}:{} matches multiple candidates", ticket, name))?,
+        }
+    }
+
+    Ok(columns)
+}
+
+/// Lower case a name and collapse its whitespace, so it can be compared exactly.
+fn normalise_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Parse a row from a preferences file with one column per preference.
+pub fn parse_labelled_ballot(
+    raw_row: csv::Result<csv::StringRecord>,
+    file_name: &str,
+    groups: &[Group],
+    candidates: &[CandidateId],
+    constraints: &Constraints,
+) -> IOBallot {
+    let row = match raw_row {
+        Ok(row) => row,
+        // CSV errors describe their own position.
+        Err(e) => return Err(InputError(Box::new(RowError::new(file_name, None, e)))),
+    };
+    let prefs = row.iter().skip(NUM_INFO_COLUMNS).collect::<Vec<_>>().join(",");
+    parse_ballot_str(&prefs, groups, candidates, constraints)
+        .map_err(|e| match e {
+            InputError(e) => {
+                let line = row.position().map(|p| p.line());
+                let mut err = RowError::new(file_name, line, e);
+                // Batch and paper numbers are the last of the info columns.
+                if let (Some(batch), Some(paper)) = (row.get(4), row.get(5)) {
+                    err = err.with_ballot_id(batch, paper);
+                }
+                InputError(Box::new(err))
+            }
+            e => e,
+        })
+}
+
+/// Default number of rows in each chunk read by `parse_rows_parallel`.
+pub const DEFAULT_CHUNK_SIZE: usize = 50_000;
+
+/// Parse the rows of a preferences file in parallel, using `parse_row` on each row.
+///
+/// Rows are read sequentially in chunks of `chunk_size`, and the rows of each chunk are parsed
+/// on the rayon thread pool. Ballots are returned in file order, so the result is the same as
+/// parsing sequentially.
+pub fn parse_rows_parallel<R, F>(
+    reader: &mut csv::Reader<R>,
+    chunk_size: usize,
+    parse_row: F,
+) -> Vec<IOBallot>
+where
+    R: Read,
+    F: Fn(csv::Result<csv::StringRecord>) -> IOBallot + Sync,
+{
+    let mut ballots = vec![];
+    let mut records = reader.records();
+    loop {
+        let chunk: Vec<_> = records.by_ref().take(chunk_size).collect();
+        if chunk.is_empty() {
+            break;
+        }
+        ballots.par_extend(chunk.into_par_iter().map(&parse_row));
     }
+    ballots
 }
 
 // NOTE: This macro is to avoid writing the iterator type.
@@ -38,10 +194,138 @@ pub fn parse_single_ballot(
 // See: https://gist.github.com/michaelsproul/20e18f52fc1be2bd05b2767ab6bd166c
 #[macro_export]
 macro_rules! parse_preferences_file {
-    ($reader:expr, $groups:expr, $candidates:expr, $constraints:expr, $experiment_num:expr) => {{
-        use $crate::parse::prefs2016::{PrefRow, parse_single_ballot};
+    ($reader:expr, $file_name:expr, $groups:expr, $candidates:expr, $constraints:expr) => {{
+        use $crate::parse::prefs2016::parse_single_ballot;
+        let (file_name, groups, candidates, constraints) =
+            ($file_name, $groups, $candidates, $constraints);
+        // Header errors are reported by the records iterator.
+        let headers = $reader.headers().ok().cloned();
+        $reader
+            .records()
+            .map(move |raw_row| {
+                parse_single_ballot(
+                    raw_row,
+                    headers.as_ref(),
+                    file_name,
+                    groups,
+                    candidates,
+                    constraints,
+                )
+            })
+    }}
+}
+
+/// Like `parse_preferences_file!`, but for files with one labelled column per preference.
+///
+/// The `candidates` should be the column order returned by `candidate_columns`.
+#[macro_export]
+macro_rules! parse_labelled_preferences_file {
+    ($reader:expr, $file_name:expr, $groups:expr, $candidates:expr, $constraints:expr) => {{
+        use $crate::parse::prefs2016::parse_labelled_ballot;
+        let (file_name, groups, candidates, constraints) =
+            ($file_name, $groups, $candidates, $constraints);
         $reader
-            .deserialize::<PrefRow>()
-            .map(|raw_row| parse_single_ballot(raw_row, $groups, $candidates, $constraints, $experiment_num))
+            .records()
+            .map(move |raw_row| {
+                parse_labelled_ballot(raw_row, file_name, groups, candidates, constraints)
+            })
     }}
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use group::get_group_list;
+
+    fn candidate(id: CandidateId, ticket: &str, surname: &str, other_names: &str) -> Candidate {
+        Candidate {
+            id: id,
+            surname: surname.to_string(),
+            other_names: other_names.to_string(),
+            group_name: ticket.to_string(),
+            party: String::new(),
+            state: "NT".to_string(),
+        }
+    }
+
+    #[test]
+    fn labelled_columns() {
+        let all = vec![
+            candidate(7, "A", "SMITH", "Jane"),
+            candidate(8, "A", "SMITH", "John"),
+            candidate(3, "UG", "JONES", "Bob"),
+        ];
+        let groups = get_group_list(&all, "NT");
+        let candidates = get_state_candidates(&all, "NT");
+        let mut header = csv::StringRecord::from(vec!["", "", "", "", "", ""]);
+        for label in &["A:Party", "UG:JONES Bob", "A:SMITH John", "A:SMITH Jane"] {
+            header.push_field(label);
+        }
+        assert!(has_labelled_columns(&header));
+        assert_eq!(
+            candidate_columns(&header, &groups, &candidates).unwrap(),
+            vec![3, 8, 7]
+        );
+
+        // Ungrouped candidates can't be voted for above the line.
+        let mut bad_header = csv::StringRecord::from(vec!["", "", "", "", "", ""]);
+        for label in &["UG:Independent", "UG:JONES Bob", "A:SMITH John", "A:SMITH Jane"] {
+            bad_header.push_field(label);
+        }
+        assert!(candidate_columns(&bad_header, &groups, &candidates).is_err());
+
+        // Names only match exactly, so a prefix of another candidate's name doesn't count.
+        let all = vec![
+            candidate(0, "A", "SMITH", "Jo"),
+            candidate(1, "A", "SMITHSON", "Jo Anne"),
+        ];
+        let groups = get_group_list(&all, "NT");
+        let candidates = get_state_candidates(&all, "NT");
+        let mut header = csv::StringRecord::from(vec!["", "", "", "", "", ""]);
+        for label in &["A:Party", "A:SMITHSON  Jo Anne", "A:smith Jo"] {
+            header.push_field(label);
+        }
+        assert_eq!(candidate_columns(&header, &groups, &candidates).unwrap(), vec![1, 0]);
+
+        let mut header = csv::StringRecord::from(vec!["", "", "", "", "", ""]);
+        for label in &["A:Party", "A:SMITHSON Jo Anne", "A:SMITH"] {
+            header.push_field(label);
+        }
+        assert!(candidate_columns(&header, &groups, &candidates).is_err());
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let all = vec![
+            candidate(0, "A", "SMITH", "Jane"),
+            candidate(1, "A", "SMITH", "John"),
+            candidate(2, "B", "JONES", "Bob"),
+        ];
+        let groups = get_group_list(&all, "NT");
+        let candidate_ids = get_candidate_id_list(&all, "NT");
+        let constraints = Constraints::official();
+
+        let mut data = String::from("ElectorateNm,VoteCollectionPointNm,VoteCollectionPointId,BatchNo,PaperNo,Preferences\n");
+        let prefs = ["1,2,,,", "2,1,,,", ",,1,2,3", "1,,,,", ",,3,1,2", ",,1,1,2", "x,,,,"];
+        for i in 0..100 {
+            data.push_str(&format!("E,P,1,1,{},\"{}\"\n", i, prefs[i % prefs.len()]));
+        }
+
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let sequential: Vec<_> =
+            parse_preferences_file!(reader, "test", &groups, &candidate_ids, &constraints)
+                .map(|b| b.map(|b| b.prefs).map_err(|e| format!("{:?}", e)))
+                .collect();
+
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let headers = reader.headers().unwrap().clone();
+        let parallel: Vec<_> = parse_rows_parallel(&mut reader, 7, |row| {
+            parse_single_ballot(row, Some(&headers), "test", &groups, &candidate_ids, &constraints)
+        }).into_iter()
+            .map(|b| b.map(|b| b.prefs).map_err(|e| format!("{:?}", e)))
+            .collect();
+
+        assert_eq!(sequential.len(), 100);
+        assert_eq!(sequential, parallel);
+    }
+}
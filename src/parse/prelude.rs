@@ -1,6 +1,6 @@
 pub use std::error::Error;
 pub use std::io::Read;
-pub use std::collections::{HashMap, HashSet, BTreeMap};
+pub use std::collections::{HashMap, BTreeMap};
 
 pub use candidate::*;
 pub use ballot_parse::*;
@@ -0,0 +1,60 @@
+//! Parsers for South Australian Legislative Council data from the Electoral Commission of SA.
+//!
+//! The candidate list is in the format read by `candidate_list`.
+//!
+//! The preference data is in the format read by `paper_rows`.
+
+use super::prelude::*;
+use super::paper_rows;
+use group::Group;
+
+/// State name used for SA candidates.
+pub const SA: &str = "SA";
+
+/// Parse the ECSA preference data into ballots.
+pub fn parse_preferences<R: Read>(
+    input: R,
+    file_name: &str,
+    groups: &[Group],
+    candidate_ids: &[CandidateId],
+    constraints: &Constraints,
+) -> Result<Vec<IOBallot>, Box<Error>> {
+    paper_rows::parse(input, file_name, groups, candidate_ids, constraints)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ballot::Ballot;
+    use group::get_group_list;
+    use parse::candidate_list;
+
+    #[test]
+    fn parse_example() {
+        let candidates = candidate_list::parse("\
+group,ballot_position,surname,given_names,party
+B,1,JONES,Bob,Blue
+A,1,SMITH,Jane,Red
+A,2,BROWN,Sam,Red
+UG,1,LONER,Ian,
+".as_bytes(), SA).unwrap();
+        let groups = get_group_list(&candidates, SA);
+        let candidate_ids = get_candidate_id_list(&candidates, SA);
+
+        let prefs = "\
+batch,paper,A,B,A:SMITH,A:BROWN,B:JONES,UG:LONER
+1,1,2,1,,,,
+1,2,,,1,2,3,4
+1,3,1,1,,,,
+";
+        let constraints = Constraints { choice: PreferBelow, counts: vec![MinAbove(1), MinBelow(4)] };
+        let ballots = parse_preferences(prefs.as_bytes(), "sa.csv", &groups, &candidate_ids, &constraints).unwrap();
+        assert_eq!(*ballots[0].as_ref().unwrap(), Ballot::single_above(vec![2, 0, 1]));
+        assert_eq!(*ballots[1].as_ref().unwrap(), Ballot::single(vec![0, 1, 2, 3]));
+        // Numbering both groups 1 leaves no valid preferences.
+        match ballots[2] {
+            Err(InvalidBallot(EmptyBallot)) => (),
+            ref other => panic!("expected an empty ballot, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Parser for Tasmanian House of Assembly data from the Tasmanian Electoral Commission.
+//!
+//! Each division elects seven members. The candidates of a division are in the format read by
+//! `candidate_list`, with the division name as the state. The TEC preference data for a
+//! division is in the format read by `paper_rows`, with one column per candidate in the
+//! (unrotated) ballot paper order and no group squares.
+
+use super::prelude::*;
+use super::paper_rows;
+
+/// Parse the TEC preference data for a division into ballots.
+pub fn parse_preferences<R: Read>(
+    input: R,
+    file_name: &str,
+    candidate_ids: &[CandidateId],
+    constraints: &Constraints,
+) -> Result<Vec<IOBallot>, Box<Error>> {
+    paper_rows::parse(input, file_name, &[], candidate_ids, constraints)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parse::candidate_list;
+    use rules::CountRules;
+    use voting::decide_election_with_rules;
+
+    #[test]
+    fn parse_and_count() {
+        let all_candidates = candidate_list::parse("\
+group,ballot_position,surname,given_names,party
+A,1,SMITH,Jane,Red
+A,2,BROWN,Sam,Red
+B,1,JONES,Bob,Blue
+".as_bytes(), "Bass").unwrap();
+        let candidates = get_state_candidates(&all_candidates, "Bass");
+        let candidate_ids = get_candidate_id_list(&all_candidates, "Bass");
+
+        let prefs = "\
+batch,paper,A1,A2,B1
+1,1,1,,
+1,2,1,2,
+1,3,2,1,
+1,4,,,
+";
+        let constraints = Constraints { choice: PreferBelow, counts: vec![MinBelow(1)] };
+        let ballots = parse_preferences(prefs.as_bytes(), "Bass.csv", &candidate_ids, &constraints).unwrap();
+        assert!(ballots[3].is_err());
+
+        // JONES has no votes, and is excluded without any ballots to transfer.
+        let result = decide_election_with_rules(&candidates, &[], ballots, 2, &CountRules::tas()).unwrap();
+        let elected: Vec<_> = result.senators.iter().map(|&(ref c, _)| c.id).collect();
+        assert_eq!(elected, vec![0, 1]);
+        assert_eq!(result.quota_papers[&0].len(), 2);
+    }
+}
@@ -0,0 +1,308 @@
+//! Parsers for Victorian Legislative Council data from the Victorian Electoral Commission.
+//!
+//! Each of the eight regions elects five members. Candidates are identified within a region
+//! by their group (`UG` for ungrouped candidates) and ballot position. The CSV files used are:
+//!
+//! * Candidates: `region,group,ballot_position,surname,given_names,party`
+//! * Group voting tickets: `region,owner_group,ticket_num,group,ballot_position,preference`
+//! * Ticket votes: `region,group,ticket_votes`
+//! * Below the line votes: `region,paper,group,ballot_position,preference`, one row per
+//!   numbered square, with the rows for each ballot paper consecutive.
+
+use csv;
+
+use super::prelude::*;
+use super::gvt2013::GVT;
+use super::gvt_usage2013::GVTUsage;
+use diagnostics::RowError;
+use super::marks::PaperMarks;
+
+/// Map from (region, group, ballot position) to candidate ID.
+pub type CandidatePositions = HashMap<(String, String, u32), CandidateId>;
+
+#[derive(Deserialize, Debug)]
+struct CandidateRow {
+    region: String,
+    group: String,
+    ballot_position: u32,
+    surname: String,
+    given_names: String,
+    party: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TicketRow {
+    region: String,
+    owner_group: String,
+    ticket_num: u32,
+    group: String,
+    ballot_position: u32,
+    preference: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct TicketVotesRow {
+    region: String,
+    group: String,
+    ticket_votes: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct BTLRow {
+    region: String,
+    paper: String,
+    group: String,
+    ballot_position: u32,
+    preference: String,
+}
+
+/// Parse the candidates for all regions, returning candidates in ballot paper order.
+///
+/// The region is stored as the candidate's `state`. IDs are assigned in ballot paper order.
+pub fn parse_candidates<R: Read>(input: R) -> Result<Vec<Candidate>, Box<Error>> {
+    let mut reader = csv::Reader::from_reader(input);
+
+    let mut rows = vec![];
+    for raw_row in reader.deserialize::<CandidateRow>() {
+        rows.push(raw_row?);
+    }
+
+    rows.sort_by(|r1, r2| {
+        (&r1.region, ticket_order(&r1.group), r1.ballot_position)
+            .cmp(&(&r2.region, ticket_order(&r2.group), r2.ballot_position))
+    });
+
+    let candidates = rows.into_iter()
+        .enumerate()
+        .map(|(id, row)| Candidate {
+            id: id as CandidateId,
+            surname: row.surname,
+            other_names: row.given_names,
+            group_name: row.group,
+            party: row.party,
+            state: row.region,
+        })
+        .collect();
+
+    Ok(candidates)
+}
+
+/// Index candidates by region, group and ballot position.
+///
+/// Positions are numbered from 1 within each group, in the order of `candidates`.
+pub fn candidate_positions(candidates: &[Candidate]) -> CandidatePositions {
+    let mut positions = HashMap::new();
+    let mut counts: HashMap<(&str, &str), u32> = HashMap::new();
+    for c in candidates {
+        let count = counts.entry((&c.state, &c.group_name)).or_insert(0);
+        *count += 1;
+        positions.insert((c.state.clone(), c.group_name.clone(), *count), c.id);
+    }
+    positions
+}
+
+fn find_candidate(
+    positions: &CandidatePositions,
+    region: &str,
+    group: &str,
+    ballot_position: u32,
+) -> Result<CandidateId, String> {
+    positions
+        .get(&(region.to_string(), group.to_string(), ballot_position))
+        .cloned()
+        .ok_or_else(|| {
+            format!("no candidate at position {} of group {} in {}", ballot_position, group, region)
+        })
+}
+
+/// Parse the group voting tickets for all regions.
+pub fn parse_tickets<R: Read>(input: R, positions: &CandidatePositions) -> Result<GVT, Box<Error>> {
+    let mut data: HashMap<String, HashMap<String, BTreeMap<u32, PrefMap>>> = HashMap::new();
+
+    let mut reader = csv::Reader::from_reader(input);
+
+    for result in reader.deserialize::<TicketRow>() {
+        let row = result?;
+        let candidate = find_candidate(positions, &row.region, &row.group, row.ballot_position)?;
+        data.entry(row.region)
+            .or_insert_with(HashMap::new)
+            .entry(row.owner_group)
+            .or_insert_with(BTreeMap::new)
+            .entry(row.ticket_num)
+            .or_insert_with(BTreeMap::new)
+            .insert(row.preference, candidate);
+    }
+
+    Ok(data.into_iter()
+        .map(|(region, ticket_map)| {
+            let tickets = ticket_map
+                .into_iter()
+                .map(|(group, pref_maps)| {
+                    (group, pref_maps.into_iter().map(|(_, m)| flatten_pref_map(m)).collect())
+                })
+                .collect();
+            (region, tickets)
+        })
+        .collect())
+}
+
+/// Parse the number of votes for each group's ticket, for all regions.
+pub fn parse_ticket_votes<R: Read>(input: R) -> Result<GVTUsage, Box<Error>> {
+    let mut usage: GVTUsage = HashMap::new();
+
+    let mut reader = csv::Reader::from_reader(input);
+
+    for result in reader.deserialize::<TicketVotesRow>() {
+        let row = result?;
+        let prev = usage
+            .entry(row.region)
+            .or_insert_with(HashMap::new)
+            .insert(row.group.clone(), row.ticket_votes);
+        if prev.is_some() {
+            Err(format!("ticket votes for group {} listed more than once", row.group))?;
+        }
+    }
+
+    Ok(usage)
+}
+
+/// Parse the below the line votes for a single region.
+///
+/// `candidate_ids` gives the order of the squares below the line for the region.
+pub fn parse_btl<R: Read>(
+    input: R,
+    file_name: &str,
+    region: &str,
+    candidate_ids: &[CandidateId],
+    positions: &CandidatePositions,
+    constraints: &Constraints,
+) -> Vec<IOBallot> {
+    let columns: HashMap<CandidateId, usize> = candidate_ids
+        .iter()
+        .enumerate()
+        .map(|(idx, &id)| (id, idx))
+        .collect();
+
+    let to_ballot = |prefs: String| parse_ballot_str(&prefs, &[], candidate_ids, constraints);
+
+    let mut reader = csv::Reader::from_reader(input);
+    let headers = match reader.headers() {
+        Ok(h) => h.clone(),
+        Err(e) => return vec![Err(InputError(Box::new(RowError::new(file_name, None, e))))],
+    };
+
+    let mut ballots = vec![];
+    let mut paper = PaperMarks::new(candidate_ids.len());
+
+    for raw_row in reader.records() {
+        let record = match raw_row {
+            Ok(r) => r,
+            Err(e) => {
+                ballots.push(Err(InputError(Box::new(RowError::new(file_name, None, e)))));
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line());
+
+        let column = record
+            .deserialize::<BTLRow>(Some(&headers))
+            .map_err(|e| e.to_string())
+            .and_then(|row| {
+                let id = find_candidate(positions, &row.region, &row.group, row.ballot_position)?;
+                Ok((row, id))
+            });
+
+        let (row, id) = match column {
+            Ok(x) => x,
+            Err(e) => {
+                ballots.push(Err(InputError(Box::new(RowError::new(file_name, line, e)))));
+                continue;
+            }
+        };
+
+        if row.region != region {
+            continue;
+        }
+
+        if let Some(prefs) = paper.next_paper(&row.paper) {
+            ballots.push(to_ballot(prefs));
+        }
+
+        match columns.get(&id) {
+            Some(&col) => paper.mark(col, &row.preference),
+            None => {
+                let e = format!("candidate {} is not part of {}", id, region);
+                ballots.push(Err(InputError(Box::new(RowError::new(file_name, line, e)))));
+            }
+        }
+    }
+
+    if let Some(prefs) = paper.finish() {
+        ballots.push(to_ballot(prefs));
+    }
+
+    ballots
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ballot::Ballot;
+    use parse::gvt2013::ticket_ballots;
+    use rand::thread_rng;
+
+    const CANDIDATES: &str = "\
+region,group,ballot_position,surname,given_names,party
+North,B,1,JONES,Bob,Blue
+North,A,1,SMITH,Jane,Red
+North,A,2,BROWN,Sam,Red
+North,UG,1,LONER,Ian,
+South,A,1,WHITE,Kim,Red
+";
+
+    const TICKETS: &str = "\
+region,owner_group,ticket_num,group,ballot_position,preference
+North,A,1,A,1,1
+North,A,1,A,2,2
+North,A,1,UG,1,3
+North,A,1,B,1,4
+North,B,1,B,1,1
+North,B,1,UG,1,2
+North,B,1,A,2,3
+North,B,1,A,1,4
+";
+
+    const BTL: &str = "\
+region,paper,group,ballot_position,preference
+North,1,B,1,1
+North,1,A,1,2
+North,1,A,2,3
+North,1,UG,1,4
+North,2,A,1,1
+North,2,B,1,3
+South,3,A,1,1
+";
+
+    #[test]
+    fn parse_region() {
+        let candidates = parse_candidates(CANDIDATES.as_bytes()).unwrap();
+        assert_eq!(candidates[0].surname, "SMITH");
+        assert_eq!(candidates[3].surname, "LONER");
+        let positions = candidate_positions(&candidates);
+
+        let gvt = parse_tickets(TICKETS.as_bytes(), &positions).unwrap();
+        assert_eq!(gvt["North"]["A"], vec![vec![0, 1, 3, 2]]);
+
+        let usage = parse_ticket_votes("region,group,ticket_votes\nNorth,A,3\nNorth,B,0\n".as_bytes()).unwrap();
+        let ballots = ticket_ballots(&gvt["North"], &usage["North"], &mut thread_rng()).unwrap();
+        let votes: u32 = ballots.iter().map(|b| b.as_ref().unwrap().weight()).sum();
+        assert_eq!(votes, 3);
+
+        let constraints = Constraints { choice: PreferBelow, counts: vec![MinBelow(3)] };
+        let north_ids = get_candidate_id_list(&candidates, "North");
+        let btl = parse_btl(BTL.as_bytes(), "btl.csv", "North", &north_ids, &positions, &constraints);
+        assert_eq!(btl.len(), 2);
+        assert_eq!(*btl[0].as_ref().unwrap(), Ballot::single(vec![2, 0, 1, 3]));
+        assert!(btl[1].is_err());
+    }
+}
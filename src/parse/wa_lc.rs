@@ -0,0 +1,113 @@
+//! Parser for Western Australian Legislative Council data from the WA Electoral Commission.
+//!
+//! From 2025 the Legislative Council is a single statewide electorate of 37 members.
+//!
+//! The candidate list is in the format read by `candidate_list`. The preference data uses the
+//! same layout as the AEC's labelled formal preferences files: six information columns, then
+//! one column per group labelled `A:Party Name` and one column per candidate labelled
+//! `A:SURNAME Given Names`.
+
+use csv;
+
+use super::prelude::*;
+use super::prefs2016::*;
+use group::Group;
+
+/// State name used for WA candidates.
+pub const WA: &str = "WA";
+
+/// Parse the WAEC preference data into ballots.
+pub fn parse_preferences<R: Read>(
+    input: R,
+    file_name: &str,
+    groups: &[Group],
+    candidates: &CandidateMap,
+    constraints: &Constraints,
+) -> Result<Vec<IOBallot>, Box<Error>> {
+    let mut reader = csv::Reader::from_reader(input);
+
+    // Match the columns to candidates using their labels.
+    let header = reader.headers()?.clone();
+    let candidate_ids = candidate_columns(&header, groups, candidates)?;
+
+    Ok(parse_rows_parallel(&mut reader, DEFAULT_CHUNK_SIZE, |row| {
+        parse_labelled_ballot(row, file_name, groups, &candidate_ids, constraints)
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ballot::Ballot;
+    use group::get_group_list;
+    use parse::candidate_list;
+    use rules::CountRules;
+    use rules::SurplusTransfer::InclusiveGregory;
+    use util::Int;
+    use voting::decide_election_with_rules;
+
+    #[test]
+    fn parse_and_count() {
+        let all_candidates = candidate_list::parse("\
+group,ballot_position,surname,given_names,party
+A,1,SMITH,Jane,Red
+A,2,BROWN,Sam,Red
+B,1,JONES,Bob,Blue
+UG,1,LONER,Ian,
+".as_bytes(), WA).unwrap();
+        let groups = get_group_list(&all_candidates, WA);
+        let candidates = get_state_candidates(&all_candidates, WA);
+
+        let mut prefs = String::from(
+            "State,Division,Vote Collection Point Name,Vote Collection Point ID,Batch No,Paper No,\
+             A:Red,B:Blue,A:SMITH Jane,A:BROWN Sam,B:JONES Bob,UG:LONER Ian\n",
+        );
+        for _ in 0..6 {
+            prefs.push_str("WA,WA,x,1,1,1,1,2,,,,\n");
+        }
+        for _ in 0..3 {
+            prefs.push_str("WA,WA,x,1,1,1,2,1,,,,\n");
+        }
+        prefs.push_str("WA,WA,x,1,1,1,,,,,2,1\n");
+
+        let constraints = Constraints { choice: PreferBelow, counts: vec![MinAbove(1), MinBelow(2)] };
+        let ballots =
+            parse_preferences(prefs.as_bytes(), "wa.csv", &groups, &candidates, &constraints).unwrap();
+        assert_eq!(ballots.len(), 10);
+
+        let result = decide_election_with_rules(&candidates, &[], ballots, 2, &CountRules::wa_lc()).unwrap();
+        let elected: Vec<_> = result.senators.iter().map(|&(ref c, _)| c.surname.clone()).collect();
+        assert_eq!(elected, vec!["SMITH", "JONES"]);
+    }
+
+    #[test]
+    fn weighted_surplus() {
+        let candidates = test_candidates(0..4);
+        let ballots = [(144, vec![0, 1, 2]), (108, vec![1, 3]), (96, vec![2]), (96, vec![3])];
+        let count = |rules: &CountRules| {
+            let stream = ballots
+                .iter()
+                .map(|&(n, ref prefs)| Ok(Ballot::multi(n, prefs.clone())));
+            let result = decide_election_with_rules(&candidates, &[], stream, 3, rules).unwrap();
+            result
+                .senators
+                .iter()
+                .map(|&(ref c, ref votes)| (c.id, votes.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        // The quota is 112. 0's surplus of 32 reaches 1 at 2/9 of a vote per ballot, so most of
+        // 1's surplus of 28 comes from their own first preferences, and 3 reaches a quota.
+        assert_eq!(
+            count(&CountRules::wa_lc()),
+            vec![(0, Int::from(144)), (1, Int::from(140)), (3, Int::from(117))]
+        );
+
+        // Without weighting, the 144 ballots from 0 outnumber the 108 first preferences.
+        let unweighted = CountRules { surplus: InclusiveGregory, ..CountRules::wa_lc() };
+        assert_eq!(
+            count(&unweighted),
+            vec![(0, Int::from(144)), (1, Int::from(140)), (2, Int::from(112))]
+        );
+    }
+}
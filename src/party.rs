@@ -0,0 +1,125 @@
+//! Selecting parties, or coalitions of parties, by name.
+//!
+//! A party is described by a list of patterns matched against `Candidate::party`, ignoring case.
+//! A `*` in a pattern matches any sequence of characters, so `Australian Labor Party*` matches
+//! both `Australian Labor Party` and `Australian Labor Party (Northern Territory) Branch`.
+
+use std::collections::HashSet;
+
+use candidate::*;
+use group::Group;
+
+/// Names of parties that can be used without listing their patterns.
+const BUILTIN_PARTIES: &[(&str, &[&str])] = &[
+    ("labor", &["Australian Labor Party*", "Labor"]),
+    (
+        "coalition",
+        &[
+            "Liberal",
+            "Liberal Party*",
+            "Liberal National*",
+            "Liberal & Nationals",
+            "The Nationals",
+            "National Party*",
+            "Country Liberal*",
+        ],
+    ),
+    ("greens", &["The Greens*", "Australian Greens*", "Greens*"]),
+];
+
+/// Patterns for one of the built-in parties: `labor`, `coalition` or `greens`.
+pub fn builtin_patterns(party: &str) -> Option<Vec<String>> {
+    BUILTIN_PARTIES
+        .iter()
+        .find(|&&(name, _)| name == party)
+        .map(|&(_, patterns)| patterns.iter().map(|p| p.to_string()).collect())
+}
+
+/// Check whether a party name matches a pattern.
+pub fn matches_pattern(pattern: &str, party: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let party = party.to_lowercase();
+
+    let mut pieces = pattern.split('*');
+    // The first piece must be a prefix, the last a suffix, and the rest appear in order.
+    let first = pieces.next().unwrap_or("");
+    if !party.starts_with(first) {
+        return false;
+    }
+    let mut rest = &party[first.len()..];
+    let mut last: Option<&str> = None;
+    for piece in pieces {
+        if let Some(prev) = last {
+            match rest.find(prev) {
+                Some(idx) => rest = &rest[idx + prev.len()..],
+                None => return false,
+            }
+        }
+        last = Some(piece);
+    }
+    match last {
+        Some(piece) => rest.ends_with(piece),
+        None => rest.is_empty(),
+    }
+}
+
+fn matches_any(patterns: &[String], party: &str) -> bool {
+    patterns.iter().any(|p| matches_pattern(p, party))
+}
+
+/// Find the groups belonging to a party: those with any candidate whose party matches.
+pub fn party_groups<'a>(
+    patterns: &[String],
+    groups: &'a [Group],
+    candidates: &CandidateMap,
+) -> Vec<&'a Group> {
+    groups
+        .iter()
+        .filter(|g| {
+            g.candidate_ids
+                .iter()
+                .any(|id| matches_any(patterns, &candidates[id].party))
+        })
+        .collect()
+}
+
+/// Find all of a party's candidates: the candidates of its groups, and any ungrouped candidates
+/// whose party matches.
+pub fn party_candidates(
+    patterns: &[String],
+    groups: &[Group],
+    candidates: &CandidateMap,
+) -> HashSet<CandidateId> {
+    let mut result: HashSet<CandidateId> = party_groups(patterns, groups, candidates)
+        .into_iter()
+        .flat_map(|g| g.candidate_ids.iter().cloned())
+        .collect();
+    result.extend(
+        candidates
+            .values()
+            .filter(|c| c.is_ungrouped() && matches_any(patterns, &c.party))
+            .map(|c| c.id),
+    );
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        assert!(matches_pattern("Liberal", "LIBERAL"));
+        assert!(!matches_pattern("Liberal", "Liberal Democrats"));
+        assert!(matches_pattern("Liberal National*", "Liberal National Party of Queensland"));
+        assert!(matches_pattern("*Labor*", "Australian Labor Party (ALP)"));
+        assert!(!matches_pattern("*Labor*", "Liberal"));
+        assert!(matches_pattern("a*b*c", "abbc"));
+        assert!(!matches_pattern("a*b*c", "acb"));
+
+        let coalition = builtin_patterns("coalition").unwrap();
+        assert!(matches_any(&coalition, "The Nationals"));
+        assert!(matches_any(&coalition, "Country Liberals (NT)"));
+        assert!(!matches_any(&coalition, "Liberal Democrats"));
+    }
+}
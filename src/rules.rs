@@ -0,0 +1,163 @@
+//! Counting rules for the different STV elections supported.
+//!
+//! Formality rules for ballots are described separately, by `Constraints`.
+
+use util::*;
+
+pub use self::QuotaRule::*;
+pub use self::SurplusTransfer::*;
+pub use self::ExclusionOrder::*;
+
+/// How the quota is computed from the number of formal votes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaRule {
+    /// votes / (positions + 1), rounded up.
+    RoundUp,
+    /// votes / (positions + 1), rounded down, plus 1 (the Droop quota).
+    Droop,
+}
+
+/// How an elected candidate's surplus is transferred to continuing candidates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SurplusTransfer {
+    /// All of the candidate's ballots are transferred at a single transfer value,
+    /// surplus / number of ballots, regardless of the value they were received at.
+    InclusiveGregory,
+    /// As for `InclusiveGregory`, but the transfer value is computed from the unexhausted
+    /// ballots only, and is at most 1.
+    UnexhaustedGregory,
+    /// Each parcel of ballots is transferred at the value it was received at, multiplied by
+    /// surplus / votes (the Weighted Inclusive Gregory method). The parcels are transferred
+    /// together, in a single count.
+    WeightedInclusiveGregory,
+    /// A random sample of the candidate's unexhausted ballots, equal in number to the surplus,
+    /// is transferred at full value. If the candidate was elected on a transfer, only the last
+    /// parcel of ballots received is sampled.
+    RandomSample,
+    /// Only the last parcel of ballots received is transferred, at surplus / number of
+    /// unexhausted ballots in the parcel, capped at the value they were received at (Hare-Clark).
+    LastParcel,
+}
+
+/// The order in which an excluded candidate's ballots are transferred.
+///
+/// Each group of ballots is transferred in a separate count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExclusionOrder {
+    /// Ballots are grouped by transfer value, and transferred largest value first.
+    ByValue,
+    /// Each parcel is transferred separately, in the order the parcels were received.
+    ByParcel,
+}
+
+/// Rules for counting the votes, once the formal ballots are known.
+#[derive(Clone, Copy, Debug)]
+pub struct CountRules {
+    pub quota: QuotaRule,
+    pub surplus: SurplusTransfer,
+    pub exclusion: ExclusionOrder,
+    /// Seed for choosing ballots at random, for rules that do. A random seed is used if `None`.
+    pub seed: Option<usize>,
+}
+
+impl CountRules {
+    /// Rules for the Senate, as this crate has always counted it.
+    pub fn senate() -> CountRules {
+        CountRules {
+            quota: RoundUp,
+            surplus: InclusiveGregory,
+            exclusion: ByValue,
+            seed: None,
+        }
+    }
+
+    /// Rules for the NSW Legislative Council (Constitution Act 1902, Schedule 6).
+    pub fn nsw_lc() -> CountRules {
+        CountRules {
+            quota: Droop,
+            surplus: RandomSample,
+            exclusion: ByValue,
+            seed: None,
+        }
+    }
+
+    /// Rules for the Victorian Legislative Council (Electoral Act 2002, Schedule 1).
+    pub fn vic_lc() -> CountRules {
+        CountRules {
+            quota: Droop,
+            surplus: InclusiveGregory,
+            exclusion: ByValue,
+            seed: None,
+        }
+    }
+
+    /// Rules for the South Australian Legislative Council (Electoral Act 1985, Section 95).
+    pub fn sa_lc() -> CountRules {
+        CountRules {
+            quota: Droop,
+            surplus: UnexhaustedGregory,
+            exclusion: ByValue,
+            seed: None,
+        }
+    }
+
+    /// Rules for the Western Australian Legislative Council (Electoral Act 1907, Schedule 1).
+    pub fn wa_lc() -> CountRules {
+        CountRules {
+            quota: Droop,
+            surplus: WeightedInclusiveGregory,
+            exclusion: ByValue,
+            seed: None,
+        }
+    }
+
+    /// Rules for the ACT Legislative Assembly (Electoral Act 1992, Schedule 4).
+    pub fn act() -> CountRules {
+        CountRules {
+            quota: Droop,
+            surplus: LastParcel,
+            exclusion: ByValue,
+            seed: None,
+        }
+    }
+
+    /// Rules for the Tasmanian House of Assembly (Electoral Act 2004, Schedule 4).
+    ///
+    /// Unlike the ACT, an excluded candidate's parcels are transferred in the order received.
+    pub fn tas() -> CountRules {
+        CountRules {
+            quota: Droop,
+            surplus: LastParcel,
+            exclusion: ByParcel,
+            seed: None,
+        }
+    }
+
+    /// The same rules, with ballots chosen at random using the given seed.
+    pub fn with_seed(self, seed: usize) -> CountRules {
+        CountRules {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    pub fn compute_quota(&self, num_votes: u32, num_positions: usize) -> Int {
+        match self.quota {
+            RoundUp => frac!(num_votes, num_positions + 1).ceil(),
+            Droop => Int::from(num_votes / (num_positions as u32 + 1) + 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quotas() {
+        assert_eq!(CountRules::senate().compute_quota(100, 3), Int::from(25));
+        assert_eq!(CountRules::senate().compute_quota(101, 3), Int::from(26));
+        assert_eq!(CountRules::nsw_lc().compute_quota(100, 3), Int::from(26));
+        assert_eq!(CountRules::nsw_lc().compute_quota(103, 3), Int::from(26));
+    }
+}
@@ -0,0 +1,226 @@
+//! Counterfactual changes to the groups standing in an election.
+//!
+//! A party can be withdrawn, in which case its candidates are removed from the ballot paper and
+//! from every ballot. Two parties can also be merged into a single group, as if they had run a
+//! joint ticket: above the line votes for either party become votes for the joint ticket, placed
+//! at the higher of the two parties' positions. Below the line votes are left as they are.
+//!
+//! Unlike the transforms in `transform`, a scenario changes the candidates as well as the
+//! ballots, so it's applied once, before any other transforms.
+
+use std::collections::HashSet;
+use std::mem;
+
+use ballot::Ballot;
+use ballot_cache::BallotCache;
+use candidate::*;
+use group::Group;
+use transform::*;
+
+pub use self::MergeOrder::*;
+
+/// Order of the candidates on a joint ticket.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeOrder {
+    /// Alternate between the two groups' candidates, starting with the first group.
+    Interleave,
+    /// All of the first group's candidates, followed by all of the second's.
+    Concatenate,
+}
+
+fn default_order() -> MergeOrder {
+    Interleave
+}
+
+/// Description of a merger, as written in an experiment file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Merger {
+    /// The party heading the joint ticket, whose group name is kept.
+    pub first: String,
+    pub second: String,
+    #[serde(default = "default_order")]
+    pub order: MergeOrder,
+}
+
+/// Two groups merged into one.
+#[derive(Debug, Clone)]
+struct MergedGroup {
+    /// Indices of the two groups in the original group list.
+    first: usize,
+    second: usize,
+    /// The candidates of the joint ticket, in order.
+    ticket: Vec<CandidateId>,
+}
+
+/// Number of ballots affected by a scenario.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScenarioCounts {
+    /// Ballots whose preferences were changed.
+    pub changed: usize,
+    /// Ballots removed because all of their preferences were for withdrawn candidates.
+    pub removed: usize,
+}
+
+/// Withdrawals and mergers, resolved to the groups and candidates of a single state.
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    withdrawn: HashSet<CandidateId>,
+    mergers: Vec<MergedGroup>,
+}
+
+impl Scenario {
+    /// Create a scenario from the candidates to withdraw, and pairs of groups to merge
+    /// (as indices into `groups`).
+    pub fn new(
+        withdrawn: HashSet<CandidateId>,
+        mergers: &[(usize, usize, MergeOrder)],
+        groups: &[Group],
+    ) -> Scenario {
+        let mergers = mergers
+            .iter()
+            .map(|&(first, second, order)| {
+                let a = &groups[first].candidate_ids;
+                let b = &groups[second].candidate_ids;
+                let ticket = match order {
+                    Interleave => (0..a.len().max(b.len()))
+                        .flat_map(|i| a.get(i).into_iter().chain(b.get(i)))
+                        .cloned()
+                        .collect(),
+                    Concatenate => a.iter().chain(b).cloned().collect(),
+                };
+                MergedGroup {
+                    first: first,
+                    second: second,
+                    ticket: ticket,
+                }
+            })
+            .collect();
+        Scenario {
+            withdrawn: withdrawn,
+            mergers: mergers,
+        }
+    }
+
+    /// Apply the scenario to a ballot, using the original groups to split above the line votes.
+    fn transform(&self, ballot: &mut Ballot, info: &ElectionInfo) -> bool {
+        let new_prefs: Vec<CandidateId> = if ballot.above_the_line && !self.mergers.is_empty() {
+            let mut merged_seen = vec![false; self.mergers.len()];
+            let mut prefs = vec![];
+            for unit in info.split_groups(&ballot.prefs) {
+                let group = info.group_index(unit[0]);
+                let merger = self.mergers
+                    .iter()
+                    .position(|m| group == Some(m.first) || group == Some(m.second));
+                match merger {
+                    Some(i) if merged_seen[i] => (),
+                    Some(i) => {
+                        merged_seen[i] = true;
+                        prefs.extend(&self.mergers[i].ticket);
+                    }
+                    None => prefs.extend(unit),
+                }
+            }
+            prefs
+        } else {
+            ballot.prefs.clone()
+        };
+        let new_prefs = new_prefs
+            .into_iter()
+            .filter(|c| !self.withdrawn.contains(c))
+            .collect();
+        replace_prefs(ballot, new_prefs)
+    }
+
+    /// Apply the scenario to a state's ballots and candidates.
+    pub fn apply(&self, cache: &mut BallotCache) -> ScenarioCounts {
+        let mut counts = ScenarioCounts { changed: 0, removed: 0 };
+        {
+            let candidates = get_state_candidates(&cache.candidates, &cache.key.state);
+            let info = ElectionInfo::new(&candidates, &cache.groups);
+            for ballot in &mut cache.ballots {
+                if self.transform(ballot, &info) {
+                    counts.changed += 1;
+                }
+            }
+        }
+        let before = cache.ballots.len();
+        cache.ballots.retain(|b| !b.prefs.is_empty());
+        counts.removed = before - cache.ballots.len();
+
+        self.apply_to_groups(&mut cache.groups, &mut cache.candidates);
+
+        // Keep the candidates in ballot paper order, with joint tickets together.
+        let order: Vec<CandidateId> = cache
+            .groups
+            .iter()
+            .flat_map(|g| g.candidate_ids.iter().cloned())
+            .chain(cache.candidates.iter().filter(|c| c.is_ungrouped()).map(|c| c.id))
+            .collect();
+        cache.candidates.sort_by_key(|c| order.iter().position(|&id| id == c.id));
+        cache.candidate_ids = order;
+
+        counts
+    }
+
+    /// Remove withdrawn candidates and merge groups.
+    fn apply_to_groups(&self, groups: &mut Vec<Group>, candidates: &mut Vec<Candidate>) {
+        let mut removed_groups = HashSet::new();
+        for m in &self.mergers {
+            let name = groups[m.first].name.clone();
+            for c in candidates.iter_mut() {
+                if groups[m.second].candidate_ids.contains(&c.id) {
+                    c.group_name = name.clone();
+                }
+            }
+            groups[m.first].candidate_ids = m.ticket.clone();
+            removed_groups.insert(m.second);
+        }
+
+        let old_groups = mem::replace(groups, vec![]);
+        groups.extend(
+            old_groups
+                .into_iter()
+                .enumerate()
+                .filter(|&(idx, _)| !removed_groups.contains(&idx))
+                .map(|(_, g)| g),
+        );
+        for group in groups.iter_mut() {
+            group.candidate_ids.retain(|c| !self.withdrawn.contains(c));
+        }
+        groups.retain(|g| !g.candidate_ids.is_empty());
+        candidates.retain(|c| !self.withdrawn.contains(&c.id));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ballot_cache::CacheKey;
+    use transform::TestElection;
+
+    #[test]
+    fn withdraw_and_merge() {
+        let election = TestElection::new(&["A", "A", "B", "B", "C", "UG"]);
+        let ballots = vec![
+            Ballot::single_above(vec![4, 2, 3, 0, 1]),
+            Ballot::single(vec![4, 0, 5]),
+            Ballot::single(vec![4]),
+        ];
+        let key = CacheKey::new("", "", "NT", 0);
+        let mut cache = BallotCache::new(key, election.all, election.candidate_ids, election.groups, vec![], ballots.into_iter().map(Ok)).unwrap();
+
+        let withdrawn = vec![4].into_iter().collect();
+        let scenario = Scenario::new(withdrawn, &[(0, 1, Interleave)], &cache.groups);
+        let counts = scenario.apply(&mut cache);
+
+        assert_eq!(counts, ScenarioCounts { changed: 3, removed: 1 });
+        assert_eq!(cache.ballots[0].prefs, vec![0, 2, 1, 3]);
+        assert_eq!(cache.ballots[1].prefs, vec![0, 5]);
+        assert_eq!(cache.groups.len(), 1);
+        assert_eq!(cache.groups[0].candidate_ids, vec![0, 2, 1, 3]);
+        assert_eq!(cache.candidate_ids, vec![0, 2, 1, 3, 5]);
+        assert_eq!(cache.candidates[1].id, 2);
+        assert_eq!(cache.candidates[1].group_name, "A");
+    }
+}
@@ -0,0 +1,295 @@
+//! Search for the smallest perturbation of an experiment that changes the elected candidates.
+//!
+//! One of the experiment's parameters is varied by bisection, with everything else (including
+//! the seed) held fixed. With a fixed seed, increasing a probability or fraction only alters more
+//! ballots, so the search finds the point at which the result first changes, assuming that once
+//! it has changed it stays changed. Every count uses the given seed, so the tipping point can be
+//! reproduced by running the experiment with the parameter set to the value found.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+use ballot::Ballot;
+use candidate::*;
+use experiment::Experiment;
+use transform::*;
+use voting::decide_election;
+
+/// The experiment parameter to vary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchParameter {
+    /// Probability of applying the moves to each ballot.
+    Probability,
+    /// Fraction of the `n`th swing.
+    SwingFraction(usize),
+    /// Fraction of ballots truncated by the `n`th truncation.
+    TruncationFraction(usize),
+    /// Number of groups kept on above the line votes by the `n`th truncation.
+    TruncationAbove(usize),
+    /// Number of candidates kept on below the line votes by the `n`th truncation.
+    TruncationBelow(usize),
+}
+
+use self::SearchParameter::*;
+
+/// Parse a parameter name, optionally followed by the index of the swing or truncation,
+/// e.g. `probability`, `swing`, `swing:1`, `truncation-fraction`, `truncation-above:0` or
+/// `truncation-below`.
+pub fn parse_parameter(input: &str) -> Result<SearchParameter, Box<Error>> {
+    let mut parts = input.splitn(2, ':');
+    let name = parts.next().unwrap_or("");
+    let idx = match parts.next() {
+        Some(idx) => idx.parse::<usize>()?,
+        None => 0,
+    };
+    match name {
+        "probability" => Ok(Probability),
+        "swing" => Ok(SwingFraction(idx)),
+        "truncation-fraction" => Ok(TruncationFraction(idx)),
+        "truncation-above" => Ok(TruncationAbove(idx)),
+        "truncation-below" => Ok(TruncationBelow(idx)),
+        _ => Err(format!("unknown search parameter: {}", input))?,
+    }
+}
+
+impl fmt::Display for SearchParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Probability => write!(f, "probability"),
+            SwingFraction(i) => write!(f, "fraction of swing {}", i),
+            TruncationFraction(i) => write!(f, "fraction of truncation {}", i),
+            TruncationAbove(i) => write!(f, "groups kept above the line by truncation {}", i),
+            TruncationBelow(i) => write!(f, "candidates kept below the line by truncation {}", i),
+        }
+    }
+}
+
+impl SearchParameter {
+    /// Whether the parameter is a number of preferences kept, for which smaller values are
+    /// larger perturbations.
+    pub fn is_level(&self) -> bool {
+        match *self {
+            TruncationAbove(_) | TruncationBelow(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Set the parameter in an experiment.
+    pub fn set(&self, experiment: &mut Experiment, value: f64) -> Result<(), Box<Error>> {
+        let missing = || format!("experiment has no {}", self);
+        match *self {
+            Probability => {
+                if experiment.moves.is_empty() {
+                    Err(missing())?;
+                }
+                experiment.probability = value;
+            }
+            SwingFraction(i) => {
+                experiment.swings.get_mut(i).ok_or_else(missing)?.fraction = value;
+            }
+            TruncationFraction(i) => {
+                experiment.truncations.get_mut(i).ok_or_else(missing)?.fraction = value;
+            }
+            TruncationAbove(i) => {
+                experiment.truncations.get_mut(i).ok_or_else(missing)?.above_the_line =
+                    Some(value as usize);
+            }
+            TruncationBelow(i) => {
+                experiment.truncations.get_mut(i).ok_or_else(missing)?.below_the_line =
+                    Some(value as usize);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The smallest perturbation found to change the elected candidates, for one seed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TippingPoint {
+    pub seed: usize,
+    /// The parameter's value: the smallest probability or fraction, or the largest number of
+    /// preferences kept, that changes the result.
+    pub value: f64,
+    /// Candidates elected at the tipping point, in the order they were elected.
+    pub elected: Vec<CandidateId>,
+}
+
+/// A search over one parameter of an experiment, for a single state.
+pub struct Search<'a> {
+    experiment: Experiment,
+    parameter: SearchParameter,
+    info: &'a ElectionInfo<'a>,
+    /// The state's candidates, in ballot paper order.
+    candidate_ids: &'a [CandidateId],
+    ballots: &'a [Ballot],
+    num_positions: usize,
+    /// Candidates elected from the unaltered ballots.
+    original: Vec<CandidateId>,
+}
+
+impl<'a> Search<'a> {
+    /// Set up a search, counting the unaltered ballots to compare against.
+    pub fn new(
+        experiment: &Experiment,
+        parameter: SearchParameter,
+        info: &'a ElectionInfo<'a>,
+        candidate_ids: &'a [CandidateId],
+        ballots: &'a [Ballot],
+        num_positions: usize,
+    ) -> Result<Search<'a>, Box<Error>> {
+        // Check the parameter exists before doing any counting.
+        parameter.set(&mut experiment.clone(), 1.0)?;
+
+        let stream = ballots.iter().cloned().map(Ok);
+        let result = decide_election(info.candidates, &[], stream, num_positions)?;
+        Ok(Search {
+            experiment: experiment.clone(),
+            parameter: parameter,
+            info: info,
+            candidate_ids: candidate_ids,
+            ballots: ballots,
+            num_positions: num_positions,
+            original: result.senators.iter().map(|&(ref c, _)| c.id).collect(),
+        })
+    }
+
+    /// Candidates elected from the unaltered ballots, in the order they were elected.
+    pub fn original(&self) -> &[CandidateId] {
+        &self.original
+    }
+
+    /// The experiment with the parameter set to the given value.
+    pub fn experiment_at(&self, value: f64) -> Result<Experiment, Box<Error>> {
+        let mut experiment = self.experiment.clone();
+        self.parameter.set(&mut experiment, value)?;
+        Ok(experiment)
+    }
+
+    /// Count the ballots with the parameter set to `value`, returning the elected candidates if
+    /// they differ from the original count.
+    fn count(&self, value: f64, seed: usize) -> Result<Option<Vec<CandidateId>>, Box<Error>> {
+        let experiment = self.experiment_at(value)?;
+        let mut pipeline = Pipeline::new(seed);
+        let info = self.info;
+        for transform in experiment.transforms(info.groups, info.candidates, self.candidate_ids)? {
+            pipeline.push(transform);
+        }
+
+        let result = {
+            let stream = pipeline.transform_stream(self.ballots.iter().cloned().map(Ok), self.info);
+            decide_election(self.info.candidates, &[], stream, self.num_positions)?
+        };
+        let elected: Vec<CandidateId> = result.senators.iter().map(|&(ref c, _)| c.id).collect();
+        info!("Search: {} = {} elected {:?}", self.parameter, value, elected);
+
+        let original: HashSet<&CandidateId> = self.original.iter().collect();
+        if elected.iter().collect::<HashSet<_>>() == original {
+            Ok(None)
+        } else {
+            Ok(Some(elected))
+        }
+    }
+
+    /// Find the tipping point for a seed, if the largest perturbation changes the result.
+    ///
+    /// Probabilities and fractions are found to within `tolerance`, and numbers of preferences
+    /// kept exactly.
+    pub fn tipping_point(
+        &self,
+        seed: usize,
+        tolerance: f64,
+    ) -> Result<Option<TippingPoint>, Box<Error>> {
+        let tipping_point = |value, elected| {
+            Some(TippingPoint {
+                seed: seed,
+                value: value,
+                elected: elected,
+            })
+        };
+
+        if self.parameter.is_level() {
+            // Keeping one preference is the largest perturbation, and keeping every group or
+            // candidate leaves the ballots unchanged.
+            let mut changed = match self.count(1.0, seed)? {
+                Some(elected) => (1, elected),
+                None => return Ok(None),
+            };
+            let mut unchanged = match self.parameter {
+                TruncationAbove(_) => self.info.groups.len(),
+                _ => self.info.candidates.len(),
+            };
+            while unchanged > changed.0 + 1 {
+                let mid = (changed.0 + unchanged) / 2;
+                match self.count(mid as f64, seed)? {
+                    Some(elected) => changed = (mid, elected),
+                    None => unchanged = mid,
+                }
+            }
+            Ok(tipping_point(changed.0 as f64, changed.1))
+        } else {
+            let mut changed = match self.count(1.0, seed)? {
+                Some(elected) => (1.0, elected),
+                None => return Ok(None),
+            };
+            let mut unchanged = 0.0;
+            while changed.0 - unchanged > tolerance {
+                let mid = (changed.0 + unchanged) / 2.0;
+                match self.count(mid, seed)? {
+                    Some(elected) => changed = (mid, elected),
+                    None => unchanged = mid,
+                }
+            }
+            Ok(tipping_point(changed.0, changed.1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use transform::TestElection;
+
+    #[test]
+    fn swing_tipping_point() {
+        assert_eq!(parse_parameter("swing:2").unwrap(), SwingFraction(2));
+        assert_eq!(parse_parameter("truncation-above").unwrap(), TruncationAbove(0));
+        assert!(parse_parameter("bump").is_err());
+
+        let election = TestElection::with_parties(&[("A", "Red"), ("B", "Blue"), ("C", "Green")]);
+        let candidate_ids = &election.candidate_ids;
+        let info = election.info();
+
+        // Red wins the seat until at least 2 of Red's 10 voters swing to Blue.
+        let mut ballots = vec![];
+        ballots.extend((0..10).map(|_| Ballot::single_above(vec![0, 2, 1])));
+        ballots.extend((0..8).map(|_| Ballot::single_above(vec![1, 2, 0])));
+        ballots.extend((0..2).map(|_| Ballot::single_above(vec![2, 0, 1])));
+
+        let spec = "
+            [parties]
+            red = [\"Red\"]
+            blue = [\"Blue\"]
+
+            [[swings]]
+            from = \"red\"
+            to = \"blue\"
+            fraction = 0.5
+        ";
+        let experiment = Experiment::from_toml(spec).unwrap();
+        assert!(Search::new(&experiment, Probability, &info, candidate_ids, &ballots, 1).is_err());
+
+        let search =
+            Search::new(&experiment, SwingFraction(0), &info, candidate_ids, &ballots, 1).unwrap();
+        assert_eq!(search.original(), &[0]);
+
+        let point = search.tipping_point(7, 0.01).unwrap().unwrap();
+        assert_eq!(point.seed, 7);
+        assert_eq!(point.elected, vec![1]);
+        assert!(point.value > 0.0 && point.value <= 1.0);
+
+        // Just below the tipping point, Red still wins.
+        let below = search.count(point.value - 0.01, 7).unwrap();
+        assert_eq!(below, None);
+    }
+}
@@ -1,6 +1,11 @@
 use stats::Stats;
-use util::Int;
+use util::*;
 use candidate::*;
+use manifest::InputChecksum;
+use experiment::Experiment;
+
+/// Ballots held by an elected candidate, with the value each contributes to their quota.
+pub type QuotaPapers = Vec<(Frac, Vec<CandidateId>)>;
 
 #[derive(Debug)]
 pub struct Senate {
@@ -8,6 +13,16 @@ pub struct Senate {
     pub senators: Vec<(Candidate, Int)>,
     pub tied: bool,
     pub stats: Stats,
+    /// Checksums of the input files used to compute this result.
+    pub inputs: Vec<InputChecksum>,
+    /// The experiment applied to the ballots, if any.
+    pub experiment: Option<Experiment>,
+    /// Ballots making up each member's quota, for filling casual vacancies (Hare-Clark only).
+    pub quota_papers: HashMap<CandidateId, QuotaPapers>,
+    /// Number of ballots that ran out of preferences during the count.
+    pub exhausted_ballots: u32,
+    /// Value of the exhausted ballots at the time they exhausted.
+    pub exhausted_votes: Int,
 }
 
 impl Senate {
@@ -16,6 +31,11 @@ impl Senate {
             senators: vec![],
             tied: false,
             stats: Stats::new(),
+            inputs: vec![],
+            experiment: None,
+            quota_papers: HashMap::new(),
+            exhausted_ballots: 0,
+            exhausted_votes: Int::from(0),
         }
     }
 
@@ -23,6 +43,10 @@ impl Senate {
         self.senators.push((candidates[&id].clone(), tally))
     }
 
+    pub fn is_elected(&self, id: CandidateId) -> bool {
+        self.senators.iter().any(|&(ref c, _)| c.id == id)
+    }
+
     pub fn num_elected(&self) -> usize {
         self.senators.len()
     }
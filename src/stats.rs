@@ -4,7 +4,7 @@ use util::*;
 
 pub type BallotErrorMap = HashMap<InvalidBallotErr, u32>;
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Stats {
     num_valid_votes: u32,
     invalid_votes: BallotErrorMap,
@@ -38,6 +38,11 @@ impl Stats {
     pub fn num_invalid_votes(&self) -> u32 {
         self.invalid_votes.values().sum()
     }
+
+    /// Counts of invalid votes by error (with details erased).
+    pub fn invalid_votes(&self) -> &BallotErrorMap {
+        &self.invalid_votes
+    }
 }
 
 impl InvalidBallotErr {
@@ -0,0 +1,82 @@
+//! Counts of the same ballots for a range of numbers of vacancies.
+//!
+//! A double dissolution fills 12 vacancies per state rather than the usual 6, which changes
+//! the quota and the order in which candidates are elected and excluded.
+
+use std::error::Error;
+
+use ballot::Ballot;
+use candidate::*;
+use voting::decide_election;
+
+/// Result of counting the ballots for one number of vacancies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VacancyCount {
+    pub vacancies: usize,
+    /// Elected candidates, in the order they were elected.
+    pub elected: Vec<CandidateId>,
+}
+
+/// Parse a number of vacancies (`6`) or an inclusive range of them (`1-12`).
+pub fn parse_vacancies(input: &str) -> Result<Vec<usize>, Box<Error>> {
+    let mut bounds = input.splitn(2, '-');
+    let min = bounds.next().unwrap_or("").trim().parse::<usize>()?;
+    let max = match bounds.next() {
+        Some(max) => max.trim().parse::<usize>()?,
+        None => min,
+    };
+    if min == 0 || min > max {
+        Err(format!("invalid range of vacancies: {}", input))?;
+    }
+    Ok((min..max + 1).collect())
+}
+
+/// Count the ballots once for each number of vacancies.
+pub fn vacancy_sweep(
+    candidates: &CandidateMap,
+    ballots: &[Ballot],
+    vacancies: &[usize],
+) -> Result<Vec<VacancyCount>, Box<Error>> {
+    let mut counts = vec![];
+    for &num_positions in vacancies {
+        if num_positions > candidates.len() {
+            Err(format!(
+                "can't fill {} vacancies with {} candidates",
+                num_positions,
+                candidates.len()
+            ))?;
+        }
+        let result = decide_election(candidates, &[], ballots.iter().cloned().map(Ok), num_positions)?;
+        info!("Counted {} vacancies", num_positions);
+
+        counts.push(VacancyCount {
+            vacancies: num_positions,
+            elected: result.senators.iter().map(|&(ref c, _)| c.id).collect(),
+        });
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sweep() {
+        assert_eq!(parse_vacancies("6").unwrap(), vec![6]);
+        assert_eq!(parse_vacancies("1-3").unwrap(), vec![1, 2, 3]);
+        assert!(parse_vacancies("0-3").is_err());
+        assert!(parse_vacancies("3-1").is_err());
+
+        let candidates = test_candidates(0..3);
+        let mut ballots = vec![];
+        ballots.extend((0..5).map(|_| Ballot::single(vec![0, 1, 2])));
+        ballots.extend((0..4).map(|_| Ballot::single(vec![1, 0])));
+        ballots.extend((0..3).map(|_| Ballot::single(vec![2, 1])));
+
+        let counts = vacancy_sweep(&candidates, &ballots, &[1, 2]).unwrap();
+        assert_eq!(counts[0], VacancyCount { vacancies: 1, elected: vec![1] });
+        assert_eq!(counts[1], VacancyCount { vacancies: 2, elected: vec![0, 1] });
+        assert!(vacancy_sweep(&candidates, &ballots, &[4]).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+//! Simulated swings of first preferences from one party to another.
+//!
+//! A fraction of the ballots whose first preference is for party A are changed so that party B
+//! is preferenced first. Which ballots are changed is decided at random, so the number of
+//! ballots actually moved is reported by the transform pipeline.
+
+use std::collections::HashSet;
+
+use rand::{Rng, StdRng};
+
+use ballot::Ballot;
+use candidate::*;
+use transform::*;
+
+pub use self::SwingMethod::*;
+
+/// How a ballot is changed to put party B first.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SwingMethod {
+    /// B's groups (or candidates) take the earliest of the positions held by A and B, and A's
+    /// the rest, with everything else left in place. If B isn't on the ballot, B's ticket is
+    /// inserted first, as for `Insert`.
+    Swap,
+    /// B's ticket (all of B's candidates, in ballot paper order) is placed first, followed by
+    /// the rest of the ballot.
+    Insert,
+}
+
+fn default_method() -> SwingMethod {
+    Swap
+}
+
+/// Description of a swing, as written in an experiment file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Swing {
+    /// The party losing first preferences.
+    pub from: String,
+    /// The party gaining first preferences.
+    pub to: String,
+    /// Chance that each ballot with a first preference for `from` is moved.
+    pub fraction: f64,
+    #[serde(default = "default_method")]
+    pub method: SwingMethod,
+}
+
+/// A swing, with the parties resolved to the candidates of a single state.
+pub struct SwingTransform {
+    name: String,
+    from: HashSet<CandidateId>,
+    to: HashSet<CandidateId>,
+    /// B's candidates in ballot paper order.
+    to_ticket: Vec<CandidateId>,
+    fraction: f64,
+    method: SwingMethod,
+}
+
+impl SwingTransform {
+    pub fn new(
+        swing: &Swing,
+        from: HashSet<CandidateId>,
+        to: HashSet<CandidateId>,
+        candidate_ids: &[CandidateId],
+    ) -> SwingTransform {
+        SwingTransform {
+            name: format!("swing from {} to {}", swing.from, swing.to),
+            to_ticket: candidate_ids.iter().cloned().filter(|c| to.contains(c)).collect(),
+            from: from,
+            to: to,
+            fraction: swing.fraction,
+            method: swing.method,
+        }
+    }
+
+    fn insert(&self, prefs: &[CandidateId]) -> Vec<CandidateId> {
+        let mut new_prefs = self.to_ticket.clone();
+        new_prefs.extend(prefs.iter().filter(|c| !self.to.contains(c)));
+        new_prefs
+    }
+
+    fn swap(&self, units: &[&[CandidateId]]) -> Vec<CandidateId> {
+        let in_party = |unit: &[CandidateId], party: &HashSet<CandidateId>| {
+            unit.iter().any(|c| party.contains(c))
+        };
+
+        // Positions held by either party, and the units to put in them: B's first.
+        let slots: Vec<usize> = (0..units.len())
+            .filter(|&i| in_party(units[i], &self.from) || in_party(units[i], &self.to))
+            .collect();
+        let to_units = slots.iter().filter(|&&i| in_party(units[i], &self.to));
+        let from_units = slots.iter().filter(|&&i| !in_party(units[i], &self.to));
+
+        let mut new_units = units.to_vec();
+        for (&slot, &i) in slots.iter().zip(to_units.chain(from_units)) {
+            new_units[slot] = units[i];
+        }
+        new_units.concat()
+    }
+}
+
+impl BallotTransform for SwingTransform {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn transform(&self, ballot: &mut Ballot, info: &ElectionInfo, rng: &mut StdRng) -> bool {
+        match ballot.prefs.first() {
+            Some(first) if self.from.contains(first) => (),
+            _ => return false,
+        }
+        if self.to_ticket.is_empty() || rng.gen::<f64>() >= self.fraction {
+            return false;
+        }
+
+        let b_on_ballot = ballot.prefs.iter().any(|c| self.to.contains(c));
+        let new_prefs = match self.method {
+            Swap if b_on_ballot => self.swap(&info.units(ballot)),
+            _ => self.insert(&ballot.prefs),
+        };
+        replace_prefs(ballot, new_prefs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn swap_and_insert() {
+        let election = TestElection::new(&["A", "A", "B", "B", "C"]);
+        let candidate_ids = &election.candidate_ids;
+        let info = election.info();
+
+        let mut swing = Swing {
+            from: "a".to_string(),
+            to: "b".to_string(),
+            fraction: 1.0,
+            method: Swap,
+        };
+        let a: HashSet<_> = vec![0, 1].into_iter().collect();
+        let b: HashSet<_> = vec![2, 3].into_iter().collect();
+        let swap = SwingTransform::new(&swing, a.clone(), b.clone(), candidate_ids);
+        swing.method = Insert;
+        let insert = SwingTransform::new(&swing, a, b, candidate_ids);
+
+        let mut rng = seeded_rng(0);
+        let run = |t: &SwingTransform, mut ballot: Ballot, rng: &mut StdRng| {
+            let changed = t.transform(&mut ballot, &info, rng);
+            (changed, ballot.prefs)
+        };
+
+        assert_eq!(run(&swap, Ballot::single_above(vec![0, 1, 4, 2, 3]), &mut rng), (true, vec![2, 3, 4, 0, 1]));
+        assert_eq!(run(&swap, Ballot::single(vec![0, 4, 3, 1]), &mut rng), (true, vec![3, 4, 0, 1]));
+        assert_eq!(run(&swap, Ballot::single(vec![0, 4]), &mut rng), (true, vec![2, 3, 0, 4]));
+        assert_eq!(run(&insert, Ballot::single(vec![0, 4, 3, 1]), &mut rng), (true, vec![2, 3, 0, 4, 1]));
+        assert_eq!(run(&insert, Ballot::single(vec![4, 0]), &mut rng), (false, vec![4, 0]));
+    }
+}
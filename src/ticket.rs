@@ -0,0 +1,177 @@
+//! Simulated group voting tickets, to estimate the result of a 2016 election under the old rules.
+//!
+//! Before 2016 a voter marking a single box above the line adopted the group's voting ticket,
+//! which numbered every candidate. Here short above the line votes are replaced by the ticket of
+//! the group given the first preference. Tickets are read from a GVT file in the 2013 format
+//! (which must use the same candidate IDs as the ballots), or synthesised from a ranking of
+//! parties: the group's own candidates, then the ranked parties in order, then every other group
+//! in ballot paper order, then the ungrouped candidates.
+//!
+//! A ticket needn't keep each group's candidates together, so ticket votes are treated as below
+//! the line votes by any transforms applied after them.
+
+use std::collections::HashSet;
+use std::error::Error;
+
+use rand::{Rng, StdRng};
+
+use ballot::Ballot;
+use candidate::*;
+use group::Group;
+use parse::gvt2013;
+use transform::*;
+use util::open_aec_csv;
+
+/// Description of the tickets to use, as written in an experiment file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TicketVoting {
+    /// GVT file in the 2013 AEC format, with tickets for some or all of the groups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Parties in order of preference, used to synthesise tickets for groups without one in
+    /// `file`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ranking: Vec<String>,
+    /// Only replace above the line votes numbering at most this many groups. By default every
+    /// above the line vote that doesn't number all of the groups is replaced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_groups: Option<usize>,
+}
+
+/// Tickets for each of a state's groups, ready to apply to ballots.
+pub struct TicketTransform {
+    /// Each group's tickets, indexed like the group list. Most groups have one.
+    tickets: Vec<Vec<Vec<CandidateId>>>,
+    max_groups: Option<usize>,
+}
+
+/// Synthesise a group's ticket from a ranking of groups.
+fn synthesise_ticket(
+    group: usize,
+    ranked: &[usize],
+    groups: &[Group],
+    candidate_ids: &[CandidateId],
+) -> Vec<CandidateId> {
+    let mut order = vec![group];
+    for g in ranked.iter().cloned().chain(0..groups.len()) {
+        if !order.contains(&g) {
+            order.push(g);
+        }
+    }
+    let mut ticket: Vec<CandidateId> = order
+        .into_iter()
+        .flat_map(|g| groups[g].candidate_ids.iter().cloned())
+        .collect();
+    let grouped: HashSet<CandidateId> = ticket.iter().cloned().collect();
+    ticket.extend(candidate_ids.iter().filter(|c| !grouped.contains(c)));
+    ticket
+}
+
+impl TicketTransform {
+    /// Load or synthesise a ticket for every group.
+    ///
+    /// `ranked` gives the indices of the groups of the ranked parties, in order, and
+    /// `candidate_ids` gives all of the state's candidates in ballot paper order.
+    pub fn new(
+        spec: &TicketVoting,
+        state: &str,
+        ranked: &[usize],
+        groups: &[Group],
+        candidate_ids: &[CandidateId],
+    ) -> Result<TicketTransform, Box<Error>> {
+        let mut tickets = vec![vec![]; groups.len()];
+
+        if let Some(ref file_name) = spec.file {
+            let gvt = gvt2013::parse(open_aec_csv(file_name)?)?;
+            let state_tickets = gvt.get(state)
+                .ok_or_else(|| format!("no voting tickets for {} in {}", state, file_name))?;
+            let all: HashSet<&CandidateId> = candidate_ids.iter().collect();
+
+            for (group, group_tickets) in groups.iter().zip(&mut tickets) {
+                if let Some(lodged) = state_tickets.get(&group.name) {
+                    for ticket in lodged {
+                        let numbered: HashSet<&CandidateId> = ticket.iter().collect();
+                        if numbered != all || ticket.len() != all.len() {
+                            Err(format!(
+                                "ticket for group {} doesn't number every candidate exactly once",
+                                group.name
+                            ))?;
+                        }
+                    }
+                    *group_tickets = lodged.clone();
+                } else {
+                    warn!("No voting ticket for group {}, synthesising one", group.name);
+                }
+            }
+        }
+
+        for (idx, group_tickets) in tickets.iter_mut().enumerate() {
+            if group_tickets.is_empty() {
+                group_tickets.push(synthesise_ticket(idx, ranked, groups, candidate_ids));
+            }
+        }
+
+        Ok(TicketTransform {
+            tickets: tickets,
+            max_groups: spec.max_groups,
+        })
+    }
+}
+
+impl BallotTransform for TicketTransform {
+    fn name(&self) -> String {
+        "group voting tickets".to_string()
+    }
+
+    fn transform(&self, ballot: &mut Ballot, info: &ElectionInfo, rng: &mut StdRng) -> bool {
+        if !ballot.above_the_line {
+            return false;
+        }
+        let num_groups = info.split_groups(&ballot.prefs).len();
+        let max_groups = self.max_groups.unwrap_or(info.groups.len() - 1);
+        let group = match ballot.prefs.first().and_then(|&c| info.group_index(c)) {
+            Some(group) if num_groups <= max_groups => group,
+            _ => return false,
+        };
+
+        // Votes for groups with several tickets are divided between them at random.
+        let group_tickets = &self.tickets[group];
+        let ticket = if group_tickets.len() == 1 {
+            &group_tickets[0]
+        } else {
+            &group_tickets[rng.gen_range(0, group_tickets.len())]
+        };
+
+        ballot.above_the_line = false;
+        replace_prefs(ballot, ticket.clone());
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use transform::TestElection;
+
+    #[test]
+    fn synthesised_tickets() {
+        let election = TestElection::new(&["A", "A", "B", "C", "UG"]);
+        let info = election.info();
+
+        let spec = TicketVoting { file: None, ranking: vec![], max_groups: None };
+        let transform = TicketTransform::new(&spec, "NT", &[2], &election.groups, &election.candidate_ids).unwrap();
+        assert_eq!(transform.tickets[0], vec![vec![0, 1, 3, 2, 4]]);
+        assert_eq!(transform.tickets[2], vec![vec![3, 0, 1, 2, 4]]);
+
+        let mut rng = seeded_rng(0);
+        let mut short = Ballot::single_above(vec![2, 0, 1]);
+        assert!(transform.transform(&mut short, &info, &mut rng));
+        assert_eq!(short.prefs, vec![2, 3, 0, 1, 4]);
+        assert!(!short.above_the_line);
+
+        let mut full = Ballot::single_above(vec![3, 2, 0, 1]);
+        assert!(!transform.transform(&mut full, &info, &mut rng));
+        let mut below = Ballot::single(vec![2]);
+        assert!(!transform.transform(&mut below, &info, &mut rng));
+    }
+}
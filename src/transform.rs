@@ -0,0 +1,254 @@
+//! Transformations of the parsed ballots, applied before counting.
+//!
+//! Transforms are combined into a `Pipeline`, which applies each in turn to every ballot and
+//! counts how many ballots each one changed. All randomness comes from the pipeline's seeded
+//! random number generator, so a run can be reproduced from its seed.
+
+use std::collections::HashMap;
+use std::mem;
+
+use rand::{SeedableRng, StdRng};
+
+use ballot::*;
+use ballot_parse::IOBallot;
+use candidate::*;
+use group::Group;
+#[cfg(test)]
+use group::get_group_list;
+
+/// Candidate and group information for the election being transformed.
+pub struct ElectionInfo<'a> {
+    pub candidates: &'a CandidateMap,
+    pub groups: &'a [Group],
+    /// Index of each grouped candidate's group.
+    group_of: HashMap<CandidateId, usize>,
+}
+
+impl<'a> ElectionInfo<'a> {
+    pub fn new(candidates: &'a CandidateMap, groups: &'a [Group]) -> ElectionInfo<'a> {
+        let group_of = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, g)| g.candidate_ids.iter().map(move |&id| (id, idx)))
+            .collect();
+        ElectionInfo {
+            candidates: candidates,
+            groups: groups,
+            group_of: group_of,
+        }
+    }
+
+    /// Index of the group a candidate belongs to, if they are grouped.
+    pub fn group_index(&self, candidate: CandidateId) -> Option<usize> {
+        self.group_of.get(&candidate).cloned()
+    }
+
+    /// Split the preferences of an above the line vote back into groups.
+    pub fn split_groups<'b>(&self, prefs: &'b [CandidateId]) -> Vec<&'b [CandidateId]> {
+        let mut groups = vec![];
+        let mut rest = prefs;
+        while !rest.is_empty() {
+            let size = self.group_index(rest[0])
+                .map_or(1, |idx| self.groups[idx].candidate_ids.len())
+                .min(rest.len());
+            let (group, tail) = rest.split_at(size);
+            groups.push(group);
+            rest = tail;
+        }
+        groups
+    }
+
+    /// Split a ballot into the units that can be moved: whole groups for above the line votes,
+    /// and single candidates for below the line votes.
+    pub fn units<'b>(&self, ballot: &'b Ballot) -> Vec<&'b [CandidateId]> {
+        if ballot.above_the_line {
+            self.split_groups(&ballot.prefs)
+        } else {
+            ballot.prefs.chunks(1).collect()
+        }
+    }
+}
+
+pub trait BallotTransform {
+    /// Name used when reporting the number of ballots changed.
+    fn name(&self) -> String;
+
+    /// Alter a single ballot, returning whether it was changed.
+    fn transform(&self, ballot: &mut Ballot, info: &ElectionInfo, rng: &mut StdRng) -> bool;
+}
+
+/// Create a random number generator from a seed.
+pub fn seeded_rng(seed: usize) -> StdRng {
+    SeedableRng::from_seed(&[seed][..])
+}
+
+/// A sequence of transforms, applied in order.
+pub struct Pipeline {
+    transforms: Vec<Box<BallotTransform>>,
+    /// Number of ballots changed by each transform.
+    counts: Vec<usize>,
+    rng: StdRng,
+}
+
+impl Pipeline {
+    pub fn new(seed: usize) -> Pipeline {
+        Pipeline {
+            transforms: vec![],
+            counts: vec![],
+            rng: seeded_rng(seed),
+        }
+    }
+
+    pub fn push(&mut self, transform: Box<BallotTransform>) {
+        self.transforms.push(transform);
+        self.counts.push(0);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Restart the random number generator from a new seed, and reset the counts.
+    pub fn reseed(&mut self, seed: usize) {
+        self.rng = seeded_rng(seed);
+        for count in &mut self.counts {
+            *count = 0;
+        }
+    }
+
+    /// Apply every transform to a ballot.
+    pub fn apply(&mut self, ballot: &mut Ballot, info: &ElectionInfo) {
+        for (transform, count) in self.transforms.iter().zip(&mut self.counts) {
+            if transform.transform(ballot, info, &mut self.rng) {
+                *count += 1;
+            }
+        }
+    }
+
+    pub fn apply_all(&mut self, ballots: &mut [Ballot], info: &ElectionInfo) {
+        for ballot in ballots {
+            self.apply(ballot, info);
+        }
+    }
+
+    /// Apply the transforms to a stream of ballots, such as the input to `decide_election`.
+    ///
+    /// Invalid ballots and errors are passed through unchanged.
+    pub fn transform_stream<'a, I>(
+        &'a mut self,
+        stream: I,
+        info: &'a ElectionInfo<'a>,
+    ) -> impl Iterator<Item = IOBallot> + 'a
+    where
+        I: IntoIterator<Item = IOBallot>,
+        I::IntoIter: 'a,
+    {
+        stream.into_iter().map(move |maybe_ballot| {
+            maybe_ballot.map(|mut ballot| {
+                self.apply(&mut ballot, info);
+                ballot
+            })
+        })
+    }
+
+    /// Name of each transform and the number of ballots it changed.
+    pub fn counts(&self) -> Vec<(String, usize)> {
+        self.transforms
+            .iter()
+            .zip(&self.counts)
+            .map(|(t, &count)| (t.name(), count))
+            .collect()
+    }
+}
+
+/// Replace a ballot's preferences, returning whether they changed.
+pub fn replace_prefs(ballot: &mut Ballot, prefs: Vec<CandidateId>) -> bool {
+    let old_prefs = mem::replace(&mut ballot.prefs, prefs);
+    old_prefs != ballot.prefs
+}
+
+/// Candidates and groups for testing transforms.
+#[cfg(test)]
+pub struct TestElection {
+    /// All candidates, in ballot paper order.
+    pub all: Vec<Candidate>,
+    pub groups: Vec<Group>,
+    pub candidates: CandidateMap,
+    pub candidate_ids: Vec<CandidateId>,
+}
+
+#[cfg(test)]
+impl TestElection {
+    /// Candidates with IDs from 0, given by group name, without parties.
+    pub fn new(group_names: &[&str]) -> TestElection {
+        let parties: Vec<_> = group_names.iter().map(|&g| (g, "")).collect();
+        TestElection::with_parties(&parties)
+    }
+
+    /// Candidates with IDs from 0, given by group name and party.
+    pub fn with_parties(candidates: &[(&str, &str)]) -> TestElection {
+        let all: Vec<Candidate> = candidates
+            .iter()
+            .enumerate()
+            .map(|(id, &(group, party))| test_candidate(id as CandidateId, group, party))
+            .collect();
+        TestElection {
+            groups: get_group_list(&all, "NT"),
+            candidates: get_state_candidates(&all, "NT"),
+            candidate_ids: get_candidate_id_list(&all, "NT"),
+            all: all,
+        }
+    }
+
+    pub fn info<'a>(&'a self) -> ElectionInfo<'a> {
+        ElectionInfo::new(&self.candidates, &self.groups)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::Rng;
+
+    /// Reverse a random half of the ballots.
+    struct Reverse;
+
+    impl BallotTransform for Reverse {
+        fn name(&self) -> String {
+            "reverse".to_string()
+        }
+
+        fn transform(&self, ballot: &mut Ballot, _: &ElectionInfo, rng: &mut StdRng) -> bool {
+            if rng.gen::<bool>() {
+                let prefs = ballot.prefs.iter().rev().cloned().collect();
+                replace_prefs(ballot, prefs)
+            } else {
+                false
+            }
+        }
+    }
+
+    #[test]
+    fn counts_and_seeds() {
+        let candidates = CandidateMap::new();
+        let info = ElectionInfo::new(&candidates, &[]);
+        let ballots: Vec<IOBallot> = (0..100).map(|_| Ok(Ballot::single(vec![1, 2]))).collect();
+
+        let mut pipeline = Pipeline::new(3);
+        pipeline.push(Box::new(Reverse));
+        pipeline.push(Box::new(Reverse));
+        let first: Vec<_> = pipeline
+            .transform_stream(ballots.iter().map(|b| Ok(b.as_ref().unwrap().clone())), &info)
+            .map(|b| b.unwrap().prefs)
+            .collect();
+        let counts = pipeline.counts();
+        assert_eq!(counts[0].0, "reverse");
+        assert!(counts[0].1 > 0 && counts[1].1 > 0);
+
+        pipeline.reseed(3);
+        let mut second: Vec<Ballot> = ballots.into_iter().map(|b| b.unwrap()).collect();
+        pipeline.apply_all(&mut second, &info);
+        assert_eq!(pipeline.counts(), counts);
+        assert!(second.iter().map(|b| &b.prefs).eq(first.iter()));
+    }
+}
@@ -0,0 +1,101 @@
+//! Simulated truncation of preferences, to measure the effect of exhaustion.
+//!
+//! Since 2016 voters need only number 6 boxes above the line or 12 below it, so many ballots
+//! exhaust before the end of the count. A truncation keeps only the first few groups of each
+//! above the line vote, or the first few candidates of each below the line vote, either on every
+//! ballot or on a random fraction of them.
+
+use rand::{Rng, StdRng};
+
+use ballot::Ballot;
+use transform::*;
+
+fn default_fraction() -> f64 {
+    1.0
+}
+
+/// Description of a truncation, as written in an experiment file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Truncation {
+    /// Number of groups to keep on above the line votes. If absent they aren't truncated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub above_the_line: Option<usize>,
+    /// Number of candidates to keep on below the line votes. If absent they aren't truncated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below_the_line: Option<usize>,
+    /// Chance that each ballot with more preferences than allowed is truncated.
+    #[serde(default = "default_fraction")]
+    pub fraction: f64,
+}
+
+/// A truncation, ready to apply to ballots.
+pub struct TruncateTransform {
+    truncation: Truncation,
+}
+
+impl TruncateTransform {
+    pub fn new(truncation: &Truncation) -> TruncateTransform {
+        TruncateTransform { truncation: truncation.clone() }
+    }
+}
+
+impl BallotTransform for TruncateTransform {
+    fn name(&self) -> String {
+        let mut limits = vec![];
+        if let Some(k) = self.truncation.above_the_line {
+            limits.push(format!("{} groups above the line", k));
+        }
+        if let Some(k) = self.truncation.below_the_line {
+            limits.push(format!("{} candidates below the line", k));
+        }
+        format!("truncation to {}", limits.join(" and "))
+    }
+
+    fn transform(&self, ballot: &mut Ballot, info: &ElectionInfo, rng: &mut StdRng) -> bool {
+        let limit = if ballot.above_the_line {
+            self.truncation.above_the_line
+        } else {
+            self.truncation.below_the_line
+        };
+        let new_prefs = {
+            let units = info.units(ballot);
+            match limit {
+                Some(k) if units.len() > k => units[..k].concat(),
+                _ => return false,
+            }
+        };
+        // Only draw random numbers for ballots that would be changed.
+        if self.truncation.fraction < 1.0 && rng.gen::<f64>() >= self.truncation.fraction {
+            return false;
+        }
+        replace_prefs(ballot, new_prefs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use transform::TestElection;
+
+    #[test]
+    fn truncate_units() {
+        let election = TestElection::new(&["A", "A", "B", "B", "C"]);
+        let info = election.info();
+
+        let truncation = Truncation { above_the_line: Some(2), below_the_line: None, fraction: 1.0 };
+        let transform = TruncateTransform::new(&truncation);
+        let mut rng = seeded_rng(0);
+
+        let mut above = Ballot::single_above(vec![2, 3, 0, 1, 4]);
+        assert!(transform.transform(&mut above, &info, &mut rng));
+        assert_eq!(above.prefs, vec![2, 3, 0, 1]);
+        assert!(!transform.transform(&mut above, &info, &mut rng));
+
+        let mut below = Ballot::single(vec![4, 3, 2]);
+        assert!(!transform.transform(&mut below, &info, &mut rng));
+
+        let below_only = Truncation { above_the_line: None, below_the_line: Some(1), fraction: 1.0 };
+        assert!(TruncateTransform::new(&below_only).transform(&mut below, &info, &mut rng));
+        assert_eq!(below.prefs, vec![4]);
+    }
+}
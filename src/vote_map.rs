@@ -3,9 +3,12 @@ use candidate::*;
 use util::*;
 use arith::*;
 use vote_log::*;
+use rules::*;
+use senate_result::QuotaPapers;
 
-use rand::{thread_rng, Rng};
+use rand::{self, thread_rng, Rng, SeedableRng, StdRng};
 use itertools::Itertools;
+use std::cmp::min;
 use std::mem;
 
 /// Map from transfer values to ballots with that transfer value.
@@ -15,13 +18,23 @@ pub type TransferMap<'a> = BTreeMap<Frac, Vec<&'a mut Ballot>>;
 pub struct VoteMap<'a> {
     info: HashMap<CandidateId, VoteInfo<'a>>,
     candidates: &'a CandidateMap,
+    rules: CountRules,
     one: Frac,
+    /// Number of ballots exhausted so far, and their value when they exhausted.
+    exhausted_ballots: u32,
+    exhausted_votes: Int,
+    /// Source of randomness for surplus sampling, seeded from the rules.
+    rng: StdRng,
 }
 
 /// Per-candidate intermediate data.
 struct VoteInfo<'a> {
     votes: VoteLog,
     ballots: TransferMap<'a>,
+    /// Transfer value and number of ballots of each parcel received, in the order received.
+    ///
+    /// The first preferences are the first parcel.
+    parcels: Vec<(Frac, usize)>,
     eliminated: bool,
 }
 
@@ -31,6 +44,10 @@ pub struct CandidateElected<'a> {
     pub id: CandidateId,
     pub votes: Int,
     pub transfers: Vec<PreferenceTransfer<'a>>,
+    /// Ballots making up the candidate's quota, if recorded by the surplus transfer method.
+    ///
+    /// Candidates elected without a quota have all of the ballots they held recorded.
+    pub quota_papers: Option<QuotaPapers>,
 }
 
 pub struct CandidateExcluded<'a> {
@@ -40,9 +57,15 @@ pub struct CandidateExcluded<'a> {
 
 impl<'a> VoteInfo<'a> {
     fn new() -> Self {
+        // Start every tally at 0, so that candidates without any first preferences have one.
+        // Adding first preferences to it leaves the same tally as starting with them, so the
+        // logs used to break ties are unchanged for every other candidate.
+        let mut votes = VoteLog::new();
+        votes.update_vote(0, Int::from(0));
         VoteInfo {
-            votes: VoteLog::new(),
+            votes: votes,
             ballots: new_transfer_map(),
+            parcels: vec![],
             eliminated: false,
         }
     }
@@ -52,6 +75,19 @@ impl<'a> VoteInfo<'a> {
     }
 }
 
+/// A ballot's preferences, and the value it contributes to a candidate's tally.
+fn paper(value: &Frac, b: &Ballot) -> (Frac, Vec<CandidateId>) {
+    (value * &frac!(b.weight()), b.prefs.clone())
+}
+
+/// All of the ballots in a transfer map, at the values they were received at.
+fn papers(transfer_map: &TransferMap) -> QuotaPapers {
+    transfer_map
+        .iter()
+        .flat_map(|(v, ballots)| ballots.iter().map(move |b| paper(v, b)))
+        .collect()
+}
+
 fn new_transfer_map<'a>() -> TransferMap<'a> {
     let mut map = TransferMap::new();
     map.insert(frac!(1), vec![]);
@@ -59,11 +95,18 @@ fn new_transfer_map<'a>() -> TransferMap<'a> {
 }
 
 impl<'a> VoteMap<'a> {
-    pub fn new(candidates: &'a CandidateMap) -> Result<VoteMap<'a>, String> {
+    pub fn new(
+        candidates: &'a CandidateMap,
+        rules: &CountRules,
+    ) -> Result<VoteMap<'a>, String> {
         let mut v = VoteMap {
             info: HashMap::new(),
             candidates: candidates,
+            rules: *rules,
             one: frac!(1),
+            exhausted_ballots: 0,
+            exhausted_votes: Int::from(0),
+            rng: SeedableRng::from_seed(&[rules.seed.unwrap_or_else(rand::random)][..]),
         };
         for &id in candidates.keys() {
             let prev = v.info.insert(id, VoteInfo::new());
@@ -85,9 +128,14 @@ impl<'a> VoteMap<'a> {
         // Add to the candidate's tally.
         info.votes.update_vote(idx, Int::from(ballot.weight()));
 
-        // Add the ballot to the appropriate bucket.
+        // Add the ballot to the appropriate bucket, and to the parcel of first preferences.
         let bucket = info.ballots.get_mut(&self.one).unwrap();
         bucket.push(ballot);
+
+        if info.parcels.is_empty() {
+            info.parcels.push((self.one.clone(), 0));
+        }
+        info.parcels[0].1 += 1;
     }
 
     /// Get the IDs of all candidates whose vote exceeds the quota.
@@ -155,6 +203,11 @@ impl<'a> VoteMap<'a> {
         None
     }
 
+    /// Number of ballots exhausted so far, and the number of votes lost with them.
+    pub fn exhausted(&self) -> (u32, Int) {
+        (self.exhausted_ballots, self.exhausted_votes.clone())
+    }
+
     pub fn num_candidates_remaining(&self) -> usize {
         self.candidates_remaining().count()
     }
@@ -167,6 +220,7 @@ impl<'a> VoteMap<'a> {
     }
 
     pub fn elect_remaining(self) -> Vec<CandidateElected<'a>> {
+        let record_papers = self.rules.surplus == LastParcel;
         self.info
             .into_iter()
             .filter(|&(_, ref info)| !info.eliminated)
@@ -175,6 +229,7 @@ impl<'a> VoteMap<'a> {
                     id: id,
                     votes: info.votes.latest().clone(),
                     transfers: vec![],
+                    quota_papers: if record_papers { Some(papers(&info.ballots)) } else { None },
                 }
             })
             .collect()
@@ -183,10 +238,13 @@ impl<'a> VoteMap<'a> {
     pub fn transfer_preferences(&mut self, idx: usize, transfer: PreferenceTransfer<'a>) {
         let PreferenceTransfer(_, transfer_val, all_ballots) = transfer;
 
-        let grouped_ballots = group_ballots_by_candidate(&*self, all_ballots);
+        let (grouped_ballots, exhausted) = group_ballots_by_candidate(&*self, all_ballots);
+
+        self.exhausted_ballots += exhausted.iter().map(|b| b.weight()).sum::<u32>();
+        self.exhausted_votes += ballot_value(&transfer_val, &exhausted);
 
         for (continuing_id, ballots) in grouped_ballots {
-            let mut info = self.info.get_mut(&continuing_id).unwrap();
+            let info = self.info.get_mut(&continuing_id).unwrap();
 
             assert!(!info.eliminated);
 
@@ -201,6 +259,8 @@ impl<'a> VoteMap<'a> {
                 );
             }
 
+            info.parcels.push((transfer_val.clone(), ballots.len()));
+
             let bucket = info.ballots
                 .entry(transfer_val.clone())
                 .or_insert_with(Vec::new);
@@ -213,57 +273,258 @@ impl<'a> VoteMap<'a> {
         let mut elected = vec![];
 
         for candidate in candidates {
-            let info = self.info.get_mut(&candidate).unwrap();
+            let (num_votes, transfer_map, last_parcel) = {
+                let info = self.info.get_mut(&candidate).unwrap();
 
-            // Mark eliminated.
-            info.eliminated = true;
+                // Mark eliminated.
+                info.eliminated = true;
 
-            let num_votes = info.votes.latest().clone();
+                (info.votes.latest().clone(), info.take_ballots(), info.parcels.pop())
+            };
 
-            // Create `PreferenceTransfer` events for each transfer value.
-            let transfer_map = info.take_ballots();
+            let surplus = &num_votes - quota;
 
-            // Collect all ballots (erasing existing transfer values).
-            let all_ballots: Vec<_> = transfer_map
-                .into_iter()
-                .flat_map(|(_, ballots)| ballots)
-                .collect();
+            let mut quota_papers = None;
 
-            let num_ballots: u32 = all_ballots.iter().map(|b| b.weight()).sum();
+            let pref_transfers = match self.rules.surplus {
+                InclusiveGregory => {
+                    // Collect all ballots (erasing existing transfer values).
+                    let all_ballots: Vec<_> = transfer_map
+                        .into_iter()
+                        .flat_map(|(_, ballots)| ballots)
+                        .collect();
 
-            // Aggregate transfer value that accounts for the ones we just threw out...
-            let transfer_value = Frac::ratio(&(&num_votes - quota), &Int::from(num_ballots));
+                    let num_ballots: u32 = all_ballots.iter().map(|b| b.weight()).sum();
 
-            let pref_transfers = vec![PreferenceTransfer(candidate, transfer_value, all_ballots)];
+                    // Aggregate transfer value that accounts for the ones we just threw out...
+                    let transfer_value = Frac::ratio(&surplus, &Int::from(num_ballots));
+
+                    vec![PreferenceTransfer(candidate, transfer_value, all_ballots)]
+                }
+                WeightedInclusiveGregory => {
+                    // Each parcel keeps its relative value: a ballot received at value `v`
+                    // is transferred at `v * surplus / votes`.
+                    let scale = Frac::ratio(&surplus, &num_votes);
+                    let mut transfers: Vec<_> = transfer_map
+                        .into_iter()
+                        .filter(|&(_, ref ballots)| !ballots.is_empty())
+                        .map(|(value, ballots)| {
+                            let transfer_value = value * &scale;
+                            PreferenceTransfer(candidate, transfer_value, ballots)
+                        })
+                        .collect();
+
+                    // Largest transfer value first, as for exclusions.
+                    transfers.reverse();
+                    transfers
+                }
+                UnexhaustedGregory => {
+                    // Exhausted ballots can't be transferred, so they're left out of the
+                    // transfer value, and exhaust with whatever the others can't carry.
+                    let (unexhausted, exhausted): (Vec<_>, Vec<_>) = transfer_map
+                        .into_iter()
+                        .flat_map(|(_, ballots)| ballots)
+                        .partition(|b| self.find_next_valid_preference(b).is_some());
+
+                    let num_ballots: u32 = unexhausted.iter().map(|b| b.weight()).sum();
+
+                    let transfer_value = if Int::from(num_ballots) > surplus {
+                        Frac::ratio(&surplus, &Int::from(num_ballots))
+                    } else {
+                        self.exhausted_votes += &surplus - Int::from(num_ballots);
+                        frac!(1)
+                    };
+                    self.exhausted_ballots += exhausted.iter().map(|b| b.weight()).sum::<u32>();
+
+                    vec![PreferenceTransfer(candidate, transfer_value, unexhausted)]
+                }
+                RandomSample => {
+                    let sample = self.sample_surplus(&surplus, transfer_map, last_parcel);
+                    vec![PreferenceTransfer(candidate, frac!(1), sample)]
+                }
+                LastParcel => {
+                    let (transfer, papers) =
+                        self.transfer_last_parcel(candidate, &surplus, transfer_map, last_parcel);
+                    quota_papers = Some(papers);
+                    vec![transfer]
+                }
+            };
 
             elected.push(CandidateElected {
                 id: candidate,
                 votes: num_votes,
                 transfers: pref_transfers,
+                quota_papers: quota_papers,
             });
         }
 
         elected
     }
 
+    /// Transfer the surplus from the last parcel of ballots received (Hare-Clark).
+    ///
+    /// The unexhausted ballots of the last parcel are transferred at surplus / number of ballots,
+    /// or the value they were received at if that is less. All other ballots are kept, and
+    /// returned along with the value they contribute to the candidate's quota.
+    fn transfer_last_parcel(
+        &self,
+        candidate: CandidateId,
+        surplus: &Int,
+        mut transfer_map: TransferMap<'a>,
+        last_parcel: Option<(Frac, usize)>,
+    ) -> (PreferenceTransfer<'a>, QuotaPapers) {
+        // A candidate elected on first preferences has a single parcel, at full value.
+        let (value, size) = last_parcel.unwrap_or_else(|| (frac!(1), transfer_map[&self.one].len()));
+        let parcel = {
+            let bucket = transfer_map.get_mut(&value).unwrap();
+            let start = bucket.len() - size;
+            bucket.split_off(start)
+        };
+
+        let (unexhausted, exhausted): (Vec<_>, Vec<_>) = parcel
+            .into_iter()
+            .partition(|b| self.find_next_valid_preference(b).is_some());
+
+        let num_ballots: u32 = unexhausted.iter().map(|b| b.weight()).sum();
+        let transfer_value = if num_ballots == 0 {
+            frac!(0)
+        } else {
+            min(value.clone(), Frac::ratio(surplus, &Int::from(num_ballots)))
+        };
+
+        let kept_value = &value - &transfer_value;
+
+        let mut quota_papers = papers(&transfer_map);
+        quota_papers.extend(exhausted.iter().map(|b| paper(&value, b)));
+        quota_papers.extend(unexhausted.iter().map(|b| paper(&kept_value, b)));
+
+        (PreferenceTransfer(candidate, transfer_value, unexhausted), quota_papers)
+    }
+
+    /// Choose a random sample of ballots to transfer at full value, for a surplus of `surplus`.
+    ///
+    /// Ballots are drawn from the last parcel received if there is one, otherwise from all
+    /// of the candidate's ballots. Exhausted ballots are never chosen, and are added to the
+    /// exhausted total along with any of the surplus left over. Each continuing candidate
+    /// receives a share of the surplus in proportion to their share of the unexhausted ballots,
+    /// and their ballots are chosen at random from those showing them as the next preference.
+    fn sample_surplus(
+        &mut self,
+        surplus: &Int,
+        mut transfer_map: TransferMap<'a>,
+        last_parcel: Option<(Frac, usize)>,
+    ) -> Vec<&'a mut Ballot> {
+        let ballots: Vec<_> = match last_parcel {
+            Some((value, size)) => {
+                let bucket = transfer_map.get_mut(&value).unwrap();
+                let start = bucket.len() - size;
+                bucket.split_off(start)
+            }
+            None => {
+                transfer_map
+                    .into_iter()
+                    .flat_map(|(_, ballots)| ballots)
+                    .collect()
+            }
+        };
+
+        // Sort the unexhausted ballots into bundles by next preference.
+        let mut bundles: BTreeMap<CandidateId, Vec<&'a mut Ballot>> = BTreeMap::new();
+        for ballot in ballots {
+            match self.find_next_valid_preference(ballot) {
+                Some(i) => {
+                    let next = ballot.prefs[i];
+                    bundles.entry(next).or_insert_with(Vec::new).push(ballot);
+                }
+                None => self.exhausted_ballots += ballot.weight(),
+            }
+        }
+
+        let num_votes = |bundle: &[&'a mut Ballot]| -> u64 {
+            bundle.iter().map(|b| b.weight() as u64).sum()
+        };
+        let total: u64 = bundles.values().map(|b| num_votes(b)).sum();
+        let surplus = Option::<u64>::from(surplus).unwrap_or(0);
+
+        if total <= surplus {
+            self.exhausted_votes += Int::from(surplus - total);
+            return bundles.into_iter().flat_map(|(_, bundle)| bundle).collect();
+        }
+
+        // Shares ignore fractions, and the remaining ballots go to the candidates with the
+        // largest remainders, with ties decided by lot.
+        let mut shares: Vec<(u64, u64)> = bundles
+            .values()
+            .map(|b| (num_votes(b) * surplus / total, num_votes(b) * surplus % total))
+            .collect();
+        let allotted: u64 = shares.iter().map(|&(share, _)| share).sum();
+        let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+        self.rng.shuffle(&mut by_remainder);
+        by_remainder.sort_by(|&i, &j| shares[j].1.cmp(&shares[i].1));
+        for &i in by_remainder.iter().take((surplus - allotted) as usize) {
+            shares[i].0 += 1;
+        }
+
+        // Weighted ballots are taken whole, so a sample may slightly exceed its share.
+        let mut sample = vec![];
+        for ((_, mut bundle), (share, _)) in bundles.into_iter().zip(shares) {
+            self.rng.shuffle(&mut bundle);
+            let mut sampled = 0;
+            for ballot in bundle {
+                if sampled >= share {
+                    break;
+                }
+                sampled += ballot.weight() as u64;
+                sample.push(ballot);
+            }
+        }
+        sample
+    }
+
     /// Panics if the `id` is not the `CandidateId` of a real candidate.
     pub fn exclude_candidate_by_id(&mut self, candidate: CandidateId) -> CandidateExcluded<'a> {
         let info = self.info.get_mut(&candidate).unwrap();
 
         info.eliminated = true;
 
-        let transfer_map = info.take_ballots();
-
-        let mut pref_transfers: Vec<_> = transfer_map
-            .into_iter()
-            .map(|(transfer_val, ballots)| {
-                PreferenceTransfer(candidate, transfer_val, ballots)
-            })
-            .collect();
-
-        // Reverse the preference transfer events so they're ordered from largest to
-        // smallest transfer value.
-        pref_transfers.reverse();
+        let mut transfer_map = info.take_ballots();
+        let parcels = mem::replace(&mut info.parcels, vec![]);
+
+        let pref_transfers = match self.rules.exclusion {
+            ByValue => {
+                let mut pref_transfers: Vec<_> = transfer_map
+                    .into_iter()
+                    .map(|(transfer_val, ballots)| {
+                        PreferenceTransfer(candidate, transfer_val, ballots)
+                    })
+                    .collect();
+
+                // Reverse the preference transfer events so they're ordered from largest to
+                // smallest transfer value.
+                pref_transfers.reverse();
+                pref_transfers
+            }
+            ByParcel => {
+                // Within each bucket, ballots are stored in the order they were received.
+                let mut pref_transfers: Vec<_> = parcels
+                    .into_iter()
+                    .map(|(transfer_val, size)| {
+                        let ballots = transfer_map
+                            .get_mut(&transfer_val)
+                            .unwrap()
+                            .drain(..size)
+                            .collect();
+                        PreferenceTransfer(candidate, transfer_val, ballots)
+                    })
+                    .collect();
+
+                // Always transfer something, so that the exclusion takes a count.
+                if pref_transfers.is_empty() {
+                    pref_transfers.push(PreferenceTransfer(candidate, frac!(1), vec![]));
+                }
+                pref_transfers
+            }
+        };
 
         CandidateExcluded {
             id: candidate,
@@ -288,3 +549,65 @@ impl<'a> VoteMap<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use voting::decide_election_with_rules;
+
+    #[test]
+    fn proportional_sample() {
+        let candidates = test_candidates(0..3);
+        let mut ballots = vec![];
+        ballots.extend((0..6).map(|_| Ballot::single(vec![0, 1])));
+        ballots.extend((0..3).map(|_| Ballot::single(vec![0, 2])));
+        ballots.push(Ballot::single(vec![0]));
+
+        let mut vote_map = VoteMap::new(&candidates, &CountRules::nsw_lc().with_seed(1)).unwrap();
+        vote_map.info.get_mut(&0).unwrap().eliminated = true;
+        let mut transfer_map = new_transfer_map();
+        transfer_map.get_mut(&frac!(1)).unwrap().extend(ballots.iter_mut());
+
+        // A surplus of 4 from 9 unexhausted ballots: 1 gets 6 * 4 / 9 = 2.67 and 2 gets 1.33,
+        // so the extra ballot goes to 1 for the larger remainder.
+        let sample = vote_map.sample_surplus(&Int::from(4), transfer_map, None);
+        let to = |id| sample.iter().filter(|b| b.prefs.get(1) == Some(&id)).count();
+        assert_eq!((to(1), to(2)), (3, 1));
+        assert_eq!(vote_map.exhausted(), (1, Int::from(0)));
+    }
+
+    #[test]
+    fn exhausted_sample() {
+        let candidates = test_candidates(0..3);
+        let mut ballots = vec![];
+        ballots.extend((0..2).map(|_| Ballot::single(vec![0, 1])));
+        ballots.extend((0..5).map(|_| Ballot::single(vec![0])));
+
+        let mut vote_map = VoteMap::new(&candidates, &CountRules::nsw_lc().with_seed(1)).unwrap();
+        vote_map.info.get_mut(&0).unwrap().eliminated = true;
+        let mut transfer_map = new_transfer_map();
+        transfer_map.get_mut(&frac!(1)).unwrap().extend(ballots.iter_mut());
+
+        // Only 2 of the surplus of 4 can be transferred, the rest exhausts with 5 ballots.
+        let sample = vote_map.sample_surplus(&Int::from(4), transfer_map, None);
+        assert_eq!(sample.len(), 2);
+        assert_eq!(vote_map.exhausted(), (5, Int::from(2)));
+    }
+
+    #[test]
+    fn exhausted_surplus() {
+        let candidates = test_candidates(0..3);
+        let ballots = [(8, vec![0]), (2, vec![0, 1]), (4, vec![1]), (3, vec![2])];
+        let stream = ballots
+            .iter()
+            .map(|&(n, ref prefs)| Ok(Ballot::multi(n, prefs.clone())));
+        let result =
+            decide_election_with_rules(&candidates, &[], stream, 2, &CountRules::sa_lc()).unwrap();
+
+        // The quota is 6. Only 2 of 0's surplus of 4 can be transferred, so 2 votes exhaust
+        // with the 8 ballots that stop at 0.
+        assert!(result.is_elected(1));
+        assert_eq!(result.exhausted_ballots, 8);
+        assert_eq!(result.exhausted_votes, Int::from(2));
+    }
+}
@@ -6,20 +6,30 @@ use candidate::*;
 use vote_map::*;
 use ballot_parse::*;
 use senate_result::*;
+use rules::CountRules;
 
 pub fn compute_quota(num_votes: u32, num_positions: usize) -> Int {
-    frac!(num_votes, num_positions + 1).ceil()
+    CountRules::senate().compute_quota(num_votes, num_positions)
 }
 
+/// Elect candidates in order, stopping once every position is filled.
+///
+/// With a small number of votes, the rounded up quota can be reached by more candidates in a
+/// single count than there are positions left, in which case those with the most votes fill them.
 fn elect_candidates<'a>(
     elected: Vec<CandidateElected<'a>>,
     result: &mut Senate,
     preference_transfers: &mut VecDeque<PreferenceTransfer<'a>>,
     candidates: &CandidateMap,
+    num_positions: usize,
 ) {
-    for c in elected {
+    let positions_remaining = num_positions - result.num_elected();
+    for c in elected.into_iter().take(positions_remaining) {
         trace!("Elected {:?} with {:?} votes", candidates[&c.id], c.votes);
         result.add_senator(c.id, c.votes, candidates);
+        if let Some(papers) = c.quota_papers {
+            result.quota_papers.insert(c.id, papers);
+        }
         preference_transfers.extend(c.transfers);
     }
 }
@@ -35,12 +45,40 @@ fn exclude_candidates<'a, 'b: 'a>(
     }
 }
 
+/// Record the ballots exhausted so far in the result.
+fn record_exhausted(result: &mut Senate, vote_map: &VoteMap) {
+    let (ballots, votes) = vote_map.exhausted();
+    result.exhausted_ballots = ballots;
+    result.exhausted_votes = votes;
+}
+
+/// Count the votes for a Senate election.
 pub fn decide_election<I>(
     candidates: &CandidateMap,
     disqualified_candidates: &[CandidateId],
     ballot_stream: I,
     num_positions: usize,
 ) -> Result<Senate, Box<Error>>
+where
+    I: IntoIterator<Item = IOBallot>,
+{
+    decide_election_with_rules(
+        candidates,
+        disqualified_candidates,
+        ballot_stream,
+        num_positions,
+        &CountRules::senate(),
+    )
+}
+
+/// Count the votes for an election according to the given counting rules.
+pub fn decide_election_with_rules<I>(
+    candidates: &CandidateMap,
+    disqualified_candidates: &[CandidateId],
+    ballot_stream: I,
+    num_positions: usize,
+    rules: &CountRules,
+) -> Result<Senate, Box<Error>>
 where
     I: IntoIterator<Item = IOBallot>,
 {
@@ -66,14 +104,14 @@ where
     }
 
     // Map from candidate IDs to numbers of votes.
-    let mut vote_map = VoteMap::new(candidates)?;
+    let mut vote_map = VoteMap::new(candidates, rules)?;
 
     // Allocate first preference votes.
     for ballot_ref in &mut ballots {
         vote_map.add(0, ballot_ref);
     }
 
-    let quota = compute_quota(result.stats.num_valid_votes(), num_positions);
+    let quota = rules.compute_quota(result.stats.num_valid_votes(), num_positions);
 
     let mut preference_transfers = VecDeque::new();
 
@@ -86,6 +124,7 @@ where
             vote_map.transfer_preferences(0, transfer);
         }
     }
+    record_exhausted(&mut result, &vote_map);
 
     info!("Count #1");
     let elected_on_first_prefs = vote_map.elect_candidates_with_quota(&quota);
@@ -94,27 +133,22 @@ where
         &mut result,
         &mut preference_transfers,
         candidates,
+        num_positions,
     );
 
     for i in 2.. {
-        info!("Count #{}", i);
-
-        // Transfer pending preferences.
-        let transfer = preference_transfers.pop_front().expect(
-            "election should terminate before running out of preferences to transfer",
-        );
-
-        trace!(
-            "Transferring preferences for {:?} at value {:?}",
-            candidates[&transfer.0],
-            transfer.1
-        );
-        vote_map.transfer_preferences(i - 1, transfer);
+        // Stop once every position is filled, as any further transfers can't change the result.
+        // Without this the count would continue until the exclusions left no candidates.
+        if result.num_elected() == num_positions {
+            break;
+        }
 
-        // Elect any candidates with a full quota, and stage their preference transfers.
-        let elected = vote_map.elect_candidates_with_quota(&quota);
-        elect_candidates(elected, &mut result, &mut preference_transfers, candidates);
+        info!("Count #{}", i);
 
+        // Exclusions are made at the start of a count rather than at the end of the previous
+        // one, so that the second count can begin with an exclusion when nobody reaches a quota
+        // on first preferences. The tallies seen by the exclusion and the count in which the
+        // excluded candidate's ballots are transferred are the same either way.
         if preference_transfers.is_empty() {
             // If the number of candidates remaining is equal to the number of positions, elect
             // them all.
@@ -126,6 +160,7 @@ where
                     &mut result,
                     &mut preference_transfers,
                     candidates,
+                    num_positions,
                 );
                 break;
             }
@@ -135,6 +170,38 @@ where
             exclude_candidates(excluded, &mut preference_transfers, candidates);
         }
 
+        // Transfer pending preferences.
+        let transfer = preference_transfers.pop_front().expect(
+            "election should terminate before running out of preferences to transfer",
+        );
+
+        trace!(
+            "Transferring preferences for {:?} at value {:?}",
+            candidates[&transfer.0],
+            transfer.1
+        );
+        let from = transfer.0;
+        vote_map.transfer_preferences(i - 1, transfer);
+
+        // A surplus transferred in several parcels is still a single count.
+        if result.is_elected(from) {
+            while preference_transfers.front().map_or(false, |t| t.0 == from) {
+                let parcel = preference_transfers.pop_front().unwrap();
+                vote_map.transfer_preferences(i - 1, parcel);
+            }
+        }
+        record_exhausted(&mut result, &vote_map);
+
+        // Elect any candidates with a full quota, and stage their preference transfers.
+        let elected = vote_map.elect_candidates_with_quota(&quota);
+        elect_candidates(
+            elected,
+            &mut result,
+            &mut preference_transfers,
+            candidates,
+            num_positions,
+        );
+
         vote_map.print_summary();
     }
 
@@ -142,3 +209,49 @@ where
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ballot::Ballot;
+
+    fn count(ballots: &[(usize, Vec<CandidateId>)], num_positions: usize) -> Vec<CandidateId> {
+        let candidates =
+            test_candidates(ballots.iter().flat_map(|&(_, ref prefs)| prefs.iter().cloned()));
+        let stream = ballots
+            .iter()
+            .flat_map(|&(n, ref prefs)| (0..n).map(move |_| Ok(Ballot::single(prefs.clone()))));
+        let result = decide_election(&candidates, &[], stream, num_positions).unwrap();
+        result.senators.iter().map(|&(ref c, _)| c.id).collect()
+    }
+
+    #[test]
+    fn elected_order() {
+        // After 3 is excluded, 2 and 4 are tied, and 4 is excluded for having had fewer votes
+        // on first preferences. 2 then stays ahead of 0 and 1 to fill the second seat.
+        let ballots = vec![
+            (10, vec![5]),
+            (5, vec![0]),
+            (4, vec![1]),
+            (3, vec![2, 4]),
+            (2, vec![4, 2]),
+            (1, vec![3, 4, 2]),
+        ];
+        assert_eq!(count(&ballots, 2), vec![5, 2]);
+
+        // Nobody reaches a quota on first preferences, and 3 has no first preferences at all.
+        let ballots = vec![(4, vec![0]), (3, vec![1, 3]), (2, vec![2, 3, 1])];
+        assert_eq!(count(&ballots, 1), vec![1]);
+    }
+
+    #[test]
+    fn filled_on_quota() {
+        // 0 is elected on first preferences and 1 on 0's surplus.
+        let ballots = vec![(10, vec![0, 1, 2]), (5, vec![1]), (6, vec![2])];
+        assert_eq!(count(&ballots, 2), vec![0, 1]);
+
+        // 0 and 2 are elected on first preferences, then 1 on 0's surplus.
+        let ballots = vec![(10, vec![0, 1, 2]), (6, vec![1, 2]), (7, vec![2]), (5, vec![3])];
+        assert_eq!(count(&ballots, 3), vec![0, 2, 1]);
+    }
+}